@@ -0,0 +1,189 @@
+//! Compares the buffered UTF-8 scan with the scan it replaced, which read
+//! one byte at a time, and with skipping records when the term is rare, also
+//! when every field is quoted.
+//!
+//! The baseline is the loop the app used to run, minus the updates it sent
+//! along the way. It didn't know about quotes or records, so it does less
+//! than the scan it's compared to.
+//!
+//! Run with `cargo bench --bench scan`.
+
+use std::collections::VecDeque;
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use csv_searcher::scanner::{ScanConfig, Scanner};
+use tokio::io::{AsyncReadExt, BufReader};
+
+const WORDS: [&str; 8] = [
+    "alpha",
+    "beta",
+    "gamma",
+    "delta",
+    "needle",
+    "haystack",
+    "12345",
+    "2024-01-31",
+];
+
+/// Roughly 16 MB of records with four columns, some of them quoted with
+/// separators, quotes and line breaks inside, or with every field quoted.
+fn input(all_quoted: bool) -> Vec<u8> {
+    let mut random = 0x2545_f491_u32;
+    let mut next = move |bound: usize| {
+        // xorshift, good enough to vary the fields
+        random ^= random << 13;
+        random ^= random >> 17;
+        random ^= random << 5;
+        random as usize % bound
+    };
+
+    let mut input = String::from("id,name,notes,city\r\n");
+    let mut record = 0;
+    while input.len() < 16 * 1024 * 1024 {
+        record += 1;
+        input.push_str(&record.to_string());
+        for _ in 0..3 {
+            input.push(',');
+            let words = (0..next(5))
+                .map(|_| WORDS[next(WORDS.len())])
+                .collect::<Vec<_>>();
+            match next(5) {
+                0 => input.push_str(&format!("\"{}, \"\"{}\"\"\n\"", words.join(" "), record)),
+                _ if all_quoted => input.push_str(&format!("\"{}\"", words.join(" "))),
+                _ => input.push_str(&words.join(" ")),
+            }
+        }
+        input.push_str("\r\n");
+    }
+    input.into_bytes()
+}
+
+/// Line number, character and byte in the line, and byte in total of every
+/// match, found the way the app did before the scanner, a byte at a time
+/// through an async reader.
+async fn baseline(input: &[u8], search_string: &str, seperator: char) -> Vec<[u64; 4]> {
+    let mut occurences = Vec::new();
+    let mut reader = BufReader::with_capacity(1024 * 1024, input);
+
+    let search_chars = search_string.to_lowercase().chars().collect::<Vec<_>>();
+    let mut line_number = 1u64;
+    let mut line_character_offset = 0u64;
+    let mut line_byte_offset = 0u64;
+    let mut total_byte_offset = 0u64;
+    let mut found = VecDeque::<char>::new();
+    let mut compare_index = 0;
+
+    let mut unicode_character_bytes = [0u8; 4];
+    while let Ok(first_byte) = reader.read_u8().await {
+        let len = match first_byte {
+            0x00..0x80 => 1,
+            0xc0..0xe0 => 2,
+            0xe0..0xf0 => 3,
+            0xf0..0xf8 => 4,
+            _ => break,
+        };
+
+        line_character_offset += 1;
+        line_byte_offset += len as u64;
+        total_byte_offset += len as u64;
+
+        unicode_character_bytes[0] = first_byte;
+        if len > 1 {
+            reader
+                .read_exact(&mut unicode_character_bytes[1..len])
+                .await
+                .unwrap();
+        }
+        let str = std::str::from_utf8(&unicode_character_bytes[..len]).unwrap();
+        let char = str.chars().next().unwrap().to_lowercase().next().unwrap();
+
+        match char {
+            '\n' => {
+                line_number += 1;
+                line_character_offset = 0;
+                line_byte_offset = 0;
+                compare_index = 0;
+                found.clear();
+            }
+            char => {
+                if char == seperator {
+                    compare_index = 0;
+                    found.clear();
+                }
+            }
+        }
+
+        if char == search_chars[compare_index] {
+            found.push_back(char);
+            compare_index += 1;
+            if compare_index >= search_chars.len() {
+                occurences.push([
+                    line_number,
+                    line_character_offset,
+                    line_byte_offset,
+                    total_byte_offset,
+                ]);
+            } else {
+                continue;
+            }
+        }
+
+        if found.is_empty() {
+            continue;
+        }
+
+        found.pop_front();
+        compare_index = 0;
+        while !found.is_empty() {
+            if search_chars[compare_index] == found[compare_index] {
+                compare_index += 1;
+                if compare_index >= found.len() {
+                    break;
+                }
+            } else {
+                compare_index = 0;
+                found.pop_front();
+            }
+        }
+        found.clear();
+    }
+
+    occurences
+}
+
+fn scan(c: &mut Criterion) {
+    let all_quoted = input(true);
+    let input = input(false);
+    let mut group = c.benchmark_group("scan");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.sample_size(10);
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    group.bench_function("baseline", |b| {
+        b.iter(|| runtime.block_on(baseline(&input, "needle", ',')).len());
+    });
+
+    for (name, term) in [
+        ("buffered", "needle"),
+        // records without a 'z' are skipped by the prefilter
+        ("rare term", "zebra"),
+    ] {
+        let scanner = Scanner::new(ScanConfig::new(term)).unwrap();
+        group.bench_function(name, |b| {
+            b.iter(|| scanner.scan(input.as_slice()).count());
+        });
+    }
+
+    // quotes only end a skip where a quoted field spans lines
+    let scanner = Scanner::new(ScanConfig::new("zebra")).unwrap();
+    group.bench_function("rare term, all quoted", |b| {
+        b.iter(|| scanner.scan(all_quoted.as_slice()).count());
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, scan);
+criterion_main!(benches);
@@ -0,0 +1,131 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Chain, Cursor, Read},
+    ops::ControlFlow,
+    path::Path,
+    sync::{Arc, atomic::AtomicU64},
+};
+
+use zip::ZipArchive;
+
+use crate::{
+    decompress::{self, Compression, Counting, Input},
+    files::FileFilter,
+};
+
+/// First bytes of a zip file, either with members or empty
+const ZIP_MAGIC_BYTES: [&[u8]; 2] = [b"PK\x03\x04", b"PK\x05\x06"];
+/// Where the magic bytes of a tar file are in its first header block
+const TAR_MAGIC_BYTES: (usize, &[u8]) = (257, b"ustar");
+const TAR_BLOCK_SIZE: u64 = 512;
+
+/// A zip or tar archive whose members are scanned without extracting them.
+///
+/// Tar archives may be compressed like any other file, zip archives need to
+/// be seekable and therefore can't.
+pub(crate) struct Archive {
+    kind: Kind,
+    // How many bytes of the archive file have been read
+    file_read: Arc<AtomicU64>,
+}
+
+enum Kind {
+    Zip(ZipArchive<BufReader<Counting<File>>>),
+    Tar(tar::Archive<TarReader>),
+}
+
+/// The decompressed tar file, whose first block has already been read to
+/// detect the archive
+type TarReader = Chain<Cursor<Vec<u8>>, Box<dyn Read>>;
+
+impl Archive {
+    /// Whether an uncompressed file starting with `start` is an archive
+    pub(crate) fn detect(start: &[u8]) -> bool {
+        let (offset, magic) = TAR_MAGIC_BYTES;
+        ZIP_MAGIC_BYTES.iter().any(|zip| start.starts_with(zip))
+            || start.get(offset..offset + magic.len()) == Some(magic)
+    }
+
+    /// Opens the file at `path` as an archive, `None` if it isn't one.
+    pub(crate) fn open(path: &Path) -> io::Result<Option<Self>> {
+        let file_read = Arc::new(AtomicU64::new(0));
+        let mut file = BufReader::new(Counting::new(File::open(path)?, file_read.clone()));
+        let start = file.fill_buf()?;
+
+        if ZIP_MAGIC_BYTES.iter().any(|magic| start.starts_with(magic)) {
+            return Ok(Some(Self {
+                kind: Kind::Zip(ZipArchive::new(file)?),
+                file_read,
+            }));
+        }
+
+        let compression = Compression::detect(start);
+        let mut reader = decompress::decompress(file, compression)?;
+        let mut first_block = Vec::new();
+        (&mut reader)
+            .take(TAR_BLOCK_SIZE)
+            .read_to_end(&mut first_block)?;
+
+        let (offset, magic) = TAR_MAGIC_BYTES;
+        if first_block.get(offset..offset + magic.len()) != Some(magic) {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            kind: Kind::Tar(tar::Archive::new(Cursor::new(first_block).chain(reader))),
+            file_read,
+        }))
+    }
+
+    /// Calls `on_member` with the name and content of every file in the
+    /// archive matching `filter`, until it breaks.
+    ///
+    /// Errors of single members are passed on, errors reading the archive
+    /// itself end the iteration.
+    pub(crate) fn for_each_member(
+        &mut self,
+        filter: &FileFilter,
+        mut on_member: impl FnMut(&str, io::Result<Input<'_>>) -> ControlFlow<()>,
+    ) -> io::Result<()> {
+        match &mut self.kind {
+            Kind::Zip(zip) => {
+                for index in 0..zip.len() {
+                    let Some(name) = zip.name_for_index(index) else {
+                        continue;
+                    };
+                    if name.ends_with('/') || !filter.matches(Path::new(name)) {
+                        continue;
+                    }
+                    let name = name.to_string();
+
+                    let member = zip
+                        .by_index(index)
+                        .map(|member| Input::member(member, self.file_read.clone()))
+                        .map_err(io::Error::from);
+                    if on_member(&name, member).is_break() {
+                        break;
+                    }
+                }
+            }
+            Kind::Tar(tar) => {
+                for entry in tar.entries()? {
+                    let entry = entry?;
+                    if !entry.header().entry_type().is_file() {
+                        continue;
+                    }
+                    let name = entry.path()?.to_string_lossy().into_owned();
+                    if !filter.matches(Path::new(&name)) {
+                        continue;
+                    }
+
+                    let member = Input::member(entry, self.file_read.clone());
+                    if on_member(&name, Ok(member)).is_break() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,391 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap::Parser;
+use csv_searcher::{
+    encoding::Encoding,
+    export,
+    scanner::{
+        CaseMode, Column, DateFormat, DecimalSeparator, Filter, InvalidSequence, MatchScope,
+        Normalization, Phonetic, ScanConfig, Scanner, SearchMode, UnparseableValue,
+    },
+};
+
+/// Searches CSV files for a string without starting the GUI.
+///
+/// Exits with 0 if something was found, 1 if nothing was found and 2 if an
+/// error occured.
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    override_usage = "csv-searcher-cli [OPTIONS] <SEARCH_STRING> <FILES>...\n       \
+                      csv-searcher-cli [OPTIONS] --terms-file <TERMS_FILE> <FILES>..."
+)]
+struct Args {
+    /// The string to search for followed by the files or directories to
+    /// scan, or only those if --terms-file is given
+    #[arg(value_name = "ARGS", required = true)]
+    args: Vec<String>,
+
+    /// Search for every line of this file instead of a single string, and
+    /// write the hits per term to stderr at the end
+    #[arg(short = 'f', long)]
+    terms_file: Option<PathBuf>,
+
+    /// Treat the search terms as regular expressions matched against each field
+    #[arg(short = 'E', long)]
+    regex: bool,
+
+    /// Treat '*' in the search terms as any number of characters and '?' as
+    /// a single one, within a field
+    #[arg(short = 'W', long, conflicts_with = "regex")]
+    wildcard: bool,
+
+    /// Also find values with up to this many characters inserted, left out
+    /// or replaced, like "Meyer" for "Meier"
+    #[arg(short = 'z', long, value_name = "MAX_DISTANCE", conflicts_with_all = ["regex", "wildcard"])]
+    fuzzy: Option<u32>,
+
+    /// Find words which sound like those of the search terms, coded by
+    /// soundex, metaphone (Double Metaphone) or cologne (Kölner Phonetik)
+    #[arg(long, value_name = "ALGORITHM", conflicts_with_all = ["regex", "wildcard", "fuzzy"])]
+    phonetic: Option<Phonetic>,
+
+    /// Treat the search terms as queries on the columns, like
+    /// 'country = DE AND (amount > 1000 OR status ~ open)', and report the
+    /// matching records
+    #[arg(short = 'Q', long, conflicts_with_all = ["regex", "wildcard", "fuzzy", "phonetic"])]
+    query: bool,
+
+    /// How letters of different case are compared: exact, lower or fold,
+    /// which also matches "ss" with "ß"
+    #[arg(long, default_value = "lower")]
+    case: CaseMode,
+
+    /// Compare terms and fields in this Unicode normalization form: none,
+    /// nfc or nfkc
+    #[arg(long, default_value = "none")]
+    normalize: Normalization,
+
+    /// Match letters regardless of their diacritics, like "Muller" with
+    /// "Müller"
+    #[arg(long)]
+    ignore_diacritics: bool,
+
+    /// Only report matches which are neither preceded nor followed by a
+    /// letter, digit or underscore
+    #[arg(short, long, conflicts_with = "field")]
+    word: bool,
+
+    /// Only report matches which span a whole field
+    #[arg(short = 'x', long)]
+    field: bool,
+
+    /// Ignore whitespace around the fields with --field
+    #[arg(long, requires = "field")]
+    trim: bool,
+
+    /// Only report matches which start after the previous one ended
+    #[arg(long)]
+    no_overlap: bool,
+
+    /// The character separating the fields of a line
+    #[arg(short, long, alias = "seperator", default_value_t = ',')]
+    separator: char,
+
+    /// The character enclosing fields which contain separators or line breaks
+    #[arg(short, long, default_value_t = '"')]
+    quote: char,
+
+    /// Don't treat any character as a quote
+    #[arg(long, conflicts_with = "quote")]
+    no_quote: bool,
+
+    /// How the input is encoded: auto, utf-8, windows-1252, latin-1, utf-16le
+    /// or utf-16be
+    #[arg(short, long, default_value = "auto")]
+    encoding: Encoding,
+
+    /// Replace invalid characters and report where they are instead of
+    /// stopping at the first one
+    #[arg(long)]
+    lossy: bool,
+
+    /// The first line of the input is data instead of column names
+    #[arg(long)]
+    no_header_row: bool,
+
+    /// Only search this column, given by name or number starting at 1; can
+    /// be repeated
+    #[arg(short, long = "column", value_name = "COLUMN")]
+    columns: Vec<Column>,
+
+    /// Only search records whose value in a column is in range, like
+    /// 'quantity < 0', 'price between 1000 and 5000' or 'ordered between
+    /// 2024-03-01 and 2024-03-31'; can be repeated. With an empty search
+    /// string the matching records are reported as a whole.
+    #[arg(long = "filter", value_name = "FILTER")]
+    filters: Vec<String>,
+
+    /// How numbers are written: point, like 1,234.56, or comma, like
+    /// 1.234,56
+    #[arg(long, default_value = "point")]
+    decimal_separator: DecimalSeparator,
+
+    /// How dates in filtered columns are written, like '%d/%m/%Y %H:%M', or
+    /// auto to detect common formats
+    #[arg(long, default_value = "auto")]
+    date_format: DateFormat,
+
+    /// Only scan files in directories matching this glob, or skip them if it
+    /// starts with '!'; can be repeated
+    #[arg(short, long = "glob", value_name = "GLOB")]
+    globs: Vec<String>,
+
+    /// Only scan members of zip and tar archives matching this glob, or skip
+    /// them if it starts with '!'; can be repeated. Without one only *.csv,
+    /// *.tsv and *.txt members are scanned
+    #[arg(short, long = "member", value_name = "GLOB")]
+    members: Vec<String>,
+
+    /// Write the occurences to this file instead of stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Don't write the CSV header line
+    #[arg(long)]
+    no_header: bool,
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let (terms, files) = match args.terms_and_files() {
+        Ok(terms_and_files) => terms_and_files,
+        Err(err) => {
+            eprintln!("{}", err);
+            return ExitCode::from(2);
+        }
+    };
+
+    let mode = if args.regex {
+        SearchMode::Regex
+    } else if args.wildcard {
+        SearchMode::Wildcard
+    } else if args.fuzzy.is_some() {
+        SearchMode::Fuzzy
+    } else if args.phonetic.is_some() {
+        SearchMode::Phonetic
+    } else if args.query {
+        SearchMode::Query
+    } else {
+        SearchMode::Text
+    };
+    let filters = match args
+        .filters
+        .iter()
+        .map(|filter| Filter::parse(filter, args.decimal_separator, &args.date_format))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(filters) => filters,
+        Err(err) => {
+            eprintln!("{}", err);
+            return ExitCode::from(2);
+        }
+    };
+    let scope = if args.word {
+        MatchScope::Word
+    } else if args.field {
+        MatchScope::Field
+    } else {
+        MatchScope::Anywhere
+    };
+    let mut config = ScanConfig::with_terms(terms)
+        .mode(mode)
+        .case(args.case)
+        .normalization(args.normalize)
+        .ignore_diacritics(args.ignore_diacritics)
+        .scope(scope)
+        .trim(args.trim)
+        .overlapping(!args.no_overlap)
+        .seperator(args.separator)
+        .quote(if args.no_quote {
+            None
+        } else {
+            Some(args.quote)
+        })
+        .has_header(!args.no_header_row)
+        .encoding(args.encoding)
+        .lossy(args.lossy)
+        .columns(args.columns.iter().cloned())
+        .filters(filters)
+        .decimal_separator(args.decimal_separator)
+        .file_patterns(args.globs.iter().cloned())
+        .member_patterns(args.members.iter().cloned());
+    if let Some(max_distance) = args.fuzzy {
+        config = config.max_distance(max_distance);
+    }
+    if let Some(phonetic) = args.phonetic {
+        config = config.phonetic(phonetic);
+    }
+    let scanner = match Scanner::new(config) {
+        Ok(scanner) => scanner,
+        Err(err) => {
+            eprintln!("{}", err);
+            return ExitCode::from(2);
+        }
+    };
+
+    let output: Box<dyn Write> = match &args.output {
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(file),
+            Err(err) => {
+                eprintln!("Failed to create {}: {}", path.display(), err);
+                return ExitCode::from(2);
+            }
+        },
+        None => Box::new(io::stdout().lock()),
+    };
+    let mut output = BufWriter::new(output);
+
+    let outcome = scan(&scanner, &files, args.no_header, &mut output).and_then(|outcome| {
+        output.flush()?;
+        Ok(outcome)
+    });
+
+    match outcome {
+        Ok(Outcome { term_hits, failed }) => {
+            let terms = &scanner.config().search_terms;
+            if terms.len() > 1 {
+                eprintln!("Hits per term:");
+                for (term, hits) in terms.iter().zip(&term_hits) {
+                    eprintln!("{}: {}", term, hits);
+                }
+            }
+
+            if failed {
+                ExitCode::from(2)
+            } else if term_hits.iter().any(|hits| *hits > 0) {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::from(1)
+            }
+        }
+        Err(err) => {
+            eprintln!("Failed to write occurences: {}", err);
+            ExitCode::from(2)
+        }
+    }
+}
+
+impl Args {
+    fn terms_and_files(&self) -> Result<(Vec<String>, Vec<PathBuf>), String> {
+        let mut args = self.args.iter();
+
+        let terms = match &self.terms_file {
+            Some(path) => {
+                let terms = std::fs::read_to_string(path)
+                    .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?
+                    .lines()
+                    .map(str::trim)
+                    .filter(|term| !term.is_empty())
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>();
+                if terms.is_empty() {
+                    return Err(format!("No search terms in {}", path.display()));
+                }
+                terms
+            }
+            None => args.next().cloned().into_iter().collect(),
+        };
+
+        let files = args.map(PathBuf::from).collect::<Vec<_>>();
+        if files.is_empty() {
+            return Err("No files to scan were given".to_string());
+        }
+
+        Ok((terms, files))
+    }
+}
+
+struct Outcome {
+    // How many occurences of every search term were found
+    term_hits: Vec<u64>,
+    failed: bool,
+}
+
+fn scan(
+    scanner: &Scanner,
+    files: &[PathBuf],
+    no_header: bool,
+    output: &mut impl Write,
+) -> io::Result<Outcome> {
+    let terms = &scanner.config().search_terms;
+    let mut outcome = Outcome {
+        term_hits: vec![0; terms.len()],
+        failed: false,
+    };
+
+    if !no_header {
+        writeln!(output, "{}", export::HEADER)?;
+    }
+
+    // large plain files are scanned in parallel, with the occurences still
+    // in file order
+    for root in files {
+        let mut updates = scanner.stream(root);
+        while let Some(update) = updates.blocking_next() {
+            let update = match update {
+                Ok(update) => update,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    outcome.failed = true;
+                    continue;
+                }
+            };
+
+            // reported as they are found, so they don't pile up in a badly
+            // damaged file
+            report_invalid(&update.path, update.invalid_sequences);
+            report_unparseable(&update.path, update.unparseable_values);
+            for occurence in update.occurences {
+                outcome.term_hits[occurence.term] += 1;
+                writeln!(output, "{}", export::row(&occurence, terms))?;
+            }
+        }
+    }
+
+    Ok(outcome)
+}
+
+fn report_invalid(path: &Path, invalid_sequences: Vec<InvalidSequence>) {
+    for invalid in invalid_sequences {
+        eprintln!(
+            "{}: Replaced {} invalid bytes at line {}, byte {}",
+            path.display(),
+            invalid.length,
+            invalid.line_number,
+            invalid.total_byte_offset
+        );
+    }
+}
+
+fn report_unparseable(path: &Path, unparseable_values: Vec<UnparseableValue>) {
+    for unparseable in unparseable_values {
+        eprintln!(
+            "{}: Can't compare \"{}\" in record {}, column {} at line {}",
+            path.display(),
+            unparseable.value,
+            unparseable.record_number,
+            unparseable
+                .column_name
+                .as_deref()
+                .map_or_else(|| unparseable.column_number.to_string(), str::to_string),
+            unparseable.line_number
+        );
+    }
+}
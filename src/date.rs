@@ -0,0 +1,342 @@
+use crate::scanner::DateFormat;
+
+/// The dates an automatically detected format may start with, each
+/// optionally followed by a time
+const AUTO_DATES: [&str; 7] = [
+    "%Y-%m-%d",
+    "%Y/%m/%d",
+    "%d.%m.%Y",
+    "%d/%m/%Y",
+    "%d %b %Y",
+    "%b %d, %Y",
+    "%a, %d %b %Y",
+];
+
+const MONTHS: [&str; 12] = [
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+const WEEKDAYS: [&str; 7] = [
+    "monday",
+    "tuesday",
+    "wednesday",
+    "thursday",
+    "friday",
+    "saturday",
+    "sunday",
+];
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// A point in time parsed from a field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Timestamp {
+    /// Seconds since 1970-01-01 00:00 UTC, taking times without an offset
+    /// as UTC
+    pub(crate) seconds: i64,
+    /// Whether a time of day was given, otherwise it's the start of the day
+    pub(crate) has_time: bool,
+}
+
+impl Timestamp {
+    /// The start of the next day, if only the date was given, so the whole
+    /// day is covered.
+    pub(crate) fn end(self) -> Option<i64> {
+        (!self.has_time).then_some(self.seconds + SECONDS_PER_DAY)
+    }
+}
+
+/// Parses a date, optionally with a time, in the format, `None` if it
+/// isn't one. Whitespace around it is ignored.
+pub(crate) fn parse(text: &str, format: &DateFormat) -> Option<Timestamp> {
+    let text = text.trim();
+    match format {
+        DateFormat::Auto => AUTO_DATES.iter().find_map(|date| {
+            let mut fields = Fields::default();
+            let rest = apply(date, text, &mut fields)?;
+            time(rest, &mut fields)?.is_empty().then_some(())?;
+            fields.timestamp()
+        }),
+        DateFormat::Pattern(pattern) => {
+            let mut fields = Fields::default();
+            apply(pattern, text, &mut fields)?
+                .is_empty()
+                .then_some(())?;
+            fields.timestamp()
+        }
+    }
+}
+
+/// Checks that a pattern only uses known specifiers and has all the fields
+/// of a date.
+pub(crate) fn validate(pattern: &str) -> Result<(), String> {
+    let mut specifiers = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(char) = chars.next() {
+        if char != '%' {
+            continue;
+        }
+        match chars.next() {
+            Some(specifier) if "YymdeHIMSfbBaApzs%".contains(specifier) => {
+                specifiers.push(specifier);
+            }
+            Some(specifier) => return Err(format!("Unknown specifier %{}", specifier)),
+            None => return Err("Expected a specifier after %".to_string()),
+        }
+    }
+
+    let has = |options: &str| specifiers.iter().any(|char| options.contains(*char));
+    if has("s") || has("Yy") && has("mbB") && has("de") {
+        Ok(())
+    } else {
+        Err("Expected %Y or %y, %m or %b and %d, or %s".to_string())
+    }
+}
+
+/// The values read by the specifiers of a format
+#[derive(Debug, Default)]
+struct Fields {
+    year: Option<i64>,
+    month: Option<i64>,
+    day: Option<i64>,
+    hour: Option<i64>,
+    minute: i64,
+    second: i64,
+    // Whether the hour is after noon, if it's given like that
+    pm: Option<bool>,
+    // Seconds ahead of UTC
+    offset: i64,
+    // Seconds since 1970, which makes the other fields unnecessary
+    epoch: Option<i64>,
+}
+
+impl Fields {
+    fn timestamp(&self) -> Option<Timestamp> {
+        if let Some(seconds) = self.epoch {
+            return Some(Timestamp {
+                seconds,
+                has_time: true,
+            });
+        }
+
+        let (year, month, day) = (self.year?, self.month?, self.day?);
+        if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+            return None;
+        }
+        let hour = match (self.hour, self.pm) {
+            (Some(hour @ 1..=12), Some(pm)) => hour % 12 + if pm { 12 } else { 0 },
+            (_, Some(_)) => return None,
+            (hour, None) => hour.unwrap_or(0),
+        };
+        // a leap second is taken as the one before it
+        if hour > 23 || self.minute > 59 || self.second > 60 {
+            return None;
+        }
+
+        Some(Timestamp {
+            seconds: days_from_civil(year, month, day) * SECONDS_PER_DAY
+                + hour * 3600
+                + self.minute * 60
+                + self.second.min(59)
+                - self.offset,
+            has_time: self.hour.is_some(),
+        })
+    }
+}
+
+/// Matches the start of `text` with the pattern, like `%d.%m.%Y`, and
+/// returns the rest of it.
+///
+/// A space in the pattern matches any amount of whitespace, `%S` also takes
+/// a fraction of a second. Letters are matched in any case.
+fn apply<'a>(pattern: &str, mut text: &'a str, fields: &mut Fields) -> Option<&'a str> {
+    let mut chars = pattern.chars();
+    while let Some(char) = chars.next() {
+        if char.is_whitespace() {
+            let trimmed = text.trim_start();
+            if trimmed.len() == text.len() {
+                return None;
+            }
+            text = trimmed;
+            continue;
+        }
+        if char != '%' {
+            let next = text.chars().next()?;
+            if !next.eq_ignore_ascii_case(&char) {
+                return None;
+            }
+            text = &text[next.len_utf8()..];
+            continue;
+        }
+
+        let specifier = chars.next()?;
+        let (value, rest) = match specifier {
+            'Y' => digits(text, 4, 4)?,
+            'y' => {
+                let (year, rest) = digits(text, 2, 2)?;
+                (if year < 69 { 2000 + year } else { 1900 + year }, rest)
+            }
+            'm' | 'd' | 'e' | 'H' | 'I' | 'M' => digits(text, 1, 2)?,
+            'S' => {
+                let (second, rest) = digits(text, 1, 2)?;
+                (second, fraction(rest))
+            }
+            'f' => digits(text, 1, 9)?,
+            'b' | 'B' => {
+                let (month, rest) = name(text, &MONTHS)?;
+                (month + 1, rest)
+            }
+            'a' | 'A' => name(text, &WEEKDAYS)?,
+            'p' => {
+                let rest = text.get(2..)?;
+                match text[..2].to_ascii_lowercase().as_str() {
+                    "am" => (0, rest),
+                    "pm" => (1, rest),
+                    _ => return None,
+                }
+            }
+            'z' => offset(text)?,
+            's' => {
+                let (negative, unsigned) = match text.strip_prefix('-') {
+                    Some(unsigned) => (true, unsigned),
+                    None => (false, text),
+                };
+                let (seconds, rest) = digits(unsigned, 1, 18)?;
+                (if negative { -seconds } else { seconds }, rest)
+            }
+            '%' => (0, text.strip_prefix('%')?),
+            _ => return None,
+        };
+
+        match specifier {
+            'Y' | 'y' => fields.year = Some(value),
+            'm' | 'b' | 'B' => fields.month = Some(value),
+            'd' | 'e' => fields.day = Some(value),
+            'H' | 'I' => fields.hour = Some(value),
+            'M' => fields.minute = value,
+            'S' => fields.second = value,
+            'p' => fields.pm = Some(value == 1),
+            'z' => fields.offset = value,
+            's' => fields.epoch = Some(value),
+            _ => {}
+        }
+        text = rest;
+    }
+
+    Some(text)
+}
+
+/// Matches an optional time after a date, like `T14:30`, ` 14:30:15.250Z` or
+/// ` 2:30 PM +01:00`, and returns the rest of the text.
+fn time<'a>(text: &'a str, fields: &mut Fields) -> Option<&'a str> {
+    if text.is_empty() {
+        return Some(text);
+    }
+    let text = match text.strip_prefix(['T', 't']) {
+        Some(rest) => rest,
+        None => apply(" ", text, fields)?,
+    };
+
+    let mut rest = apply("%H:%M", text, fields)?;
+    for optional in [":%S", " %p", "%p", " %z", "%z"] {
+        if let Some(after) = apply(optional, rest, fields) {
+            rest = after;
+        }
+    }
+    Some(rest)
+}
+
+/// Reads between `min` and `max` ASCII digits.
+fn digits(text: &str, min: usize, max: usize) -> Option<(i64, &str)> {
+    let len = text
+        .bytes()
+        .take(max)
+        .take_while(u8::is_ascii_digit)
+        .count();
+    if len < min {
+        return None;
+    }
+    Some((text[..len].parse().ok()?, &text[len..]))
+}
+
+/// Skips the fraction of a second, if there is one.
+fn fraction(text: &str) -> &str {
+    match text.strip_prefix(['.', ',']) {
+        Some(rest) if rest.starts_with(|char: char| char.is_ascii_digit()) => {
+            rest.trim_start_matches(|char: char| char.is_ascii_digit())
+        }
+        _ => text,
+    }
+}
+
+/// Reads an English name or its first three letters, in any case, and
+/// returns its index.
+fn name<'a>(text: &'a str, names: &[&str]) -> Option<(i64, &'a str)> {
+    names.iter().enumerate().find_map(|(index, name)| {
+        [*name, &name[..3]].into_iter().find_map(|name| {
+            let prefix = text.get(..name.len())?;
+            prefix
+                .eq_ignore_ascii_case(name)
+                .then(|| (index as i64, &text[name.len()..]))
+        })
+    })
+}
+
+/// Reads a UTC offset like `Z`, `UTC`, `+01:00`, `-0530` or `+02`, in
+/// seconds.
+fn offset(text: &str) -> Option<(i64, &str)> {
+    for utc in ["Z", "UTC", "GMT"] {
+        if let Some(prefix) = text.get(..utc.len())
+            && prefix.eq_ignore_ascii_case(utc)
+        {
+            return Some((0, &text[utc.len()..]));
+        }
+    }
+
+    let (sign, rest) = match text.chars().next()? {
+        '+' => (1, &text[1..]),
+        '-' => (-1, &text[1..]),
+        '\u{2212}' => (-1, &text['\u{2212}'.len_utf8()..]),
+        _ => return None,
+    };
+    let (hours, rest) = digits(rest, 2, 2)?;
+    let (minutes, rest) = match digits(rest.strip_prefix(':').unwrap_or(rest), 2, 2) {
+        Some((minutes, rest)) => (minutes, rest),
+        None => (0, rest),
+    };
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    Some((sign * (hours * 3600 + minutes * 60), rest))
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 in the proleptic Gregorian calendar, after Howard
+/// Hinnant's `days_from_civil`.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
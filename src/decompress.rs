@@ -0,0 +1,213 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use bzip2::bufread::MultiBzDecoder;
+use flate2::bufread::MultiGzDecoder;
+use liblzma::bufread::XzDecoder;
+use memmap2::Mmap;
+
+/// How many bytes of a file are buffered, or taken at once from a mapped one
+pub(crate) const BUFFER_SIZE: usize = 1024 * 1024;
+
+/// A compression format recognized by the first bytes of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+}
+
+impl Compression {
+    const MAGIC_BYTES: [(Compression, &'static [u8]); 4] = [
+        (Compression::Gzip, &[0x1f, 0x8b]),
+        (Compression::Zstd, &[0x28, 0xb5, 0x2f, 0xfd]),
+        (Compression::Bzip2, b"BZh"),
+        (Compression::Xz, &[0xfd, b'7', b'z', b'X', b'Z', 0x00]),
+    ];
+
+    pub(crate) fn detect(start: &[u8]) -> Option<Self> {
+        Self::MAGIC_BYTES
+            .iter()
+            .find(|(_, magic)| start.starts_with(magic))
+            .map(|(compression, _)| *compression)
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+            Compression::Bzip2 => "bzip2",
+            Compression::Xz => "xz",
+        }
+        .fmt(f)
+    }
+}
+
+/// A file which is decompressed while it's read, if it is compressed, or a
+/// member of an archive
+///
+/// Plain files are mapped into memory and handed out as they are, without
+/// copying them into a buffer first.
+pub struct Input<'a> {
+    source: Source<'a>,
+    compression: Option<Compression>,
+    // How many bytes have been read from the file itself, unless that's just
+    // what has been scanned
+    file_read: Option<Arc<AtomicU64>>,
+    // What had been read of the archive when this member started
+    read_before: u64,
+}
+
+enum Source<'a> {
+    // A plain file, and how much of it has been consumed
+    Mapped { map: Mmap, consumed: usize },
+    Reader(BufReader<Box<dyn Read + 'a>>),
+}
+
+impl Input<'static> {
+    pub(crate) fn new(file: File) -> io::Result<Self> {
+        let file_read = Arc::new(AtomicU64::new(0));
+        let mut file = BufReader::new(Counting::new(file, file_read.clone()));
+
+        // the longest magic bytes fit into the first read of any real file
+        let compression = Compression::detect(file.fill_buf()?);
+
+        if compression.is_none()
+            && let Some(map) = map(file.get_ref().get_ref())
+        {
+            return Ok(Self {
+                source: Source::Mapped { map, consumed: 0 },
+                compression,
+                file_read: None,
+                read_before: 0,
+            });
+        }
+
+        Ok(Self {
+            source: Source::Reader(BufReader::with_capacity(
+                BUFFER_SIZE,
+                decompress(file, compression)?,
+            )),
+            compression,
+            file_read: compression.map(|_| file_read),
+            read_before: 0,
+        })
+    }
+}
+
+impl<'a> Input<'a> {
+    /// A member of an archive, `file_read` counts the bytes of the whole
+    /// archive file.
+    pub(crate) fn member(reader: impl Read + 'a, file_read: Arc<AtomicU64>) -> Self {
+        Self {
+            source: Source::Reader(BufReader::with_capacity(BUFFER_SIZE, Box::new(reader))),
+            compression: None,
+            read_before: file_read.load(Ordering::Relaxed),
+            file_read: Some(file_read),
+        }
+    }
+
+    pub fn compression(&self) -> Option<Compression> {
+        self.compression
+    }
+
+    /// How many bytes of the file have been read since this input started,
+    /// if that differs from what has been scanned
+    pub fn file_read(&self) -> Option<u64> {
+        self.file_read
+            .as_ref()
+            .map(|file_read| file_read.load(Ordering::Relaxed) - self.read_before)
+    }
+}
+
+impl Read for Input<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl BufRead for Input<'_> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        match &mut self.source {
+            Source::Mapped { map, consumed } => Ok(&map[*consumed..]),
+            Source::Reader(reader) => reader.fill_buf(),
+        }
+    }
+
+    fn consume(&mut self, amount: usize) {
+        match &mut self.source {
+            Source::Mapped { map, consumed } => *consumed = (*consumed + amount).min(map.len()),
+            Source::Reader(reader) => reader.consume(amount),
+        }
+    }
+}
+
+/// Maps a plain file into memory, so it's read without copying it through
+/// the kernel first. `None` if it can't be mapped, like a pipe.
+pub(crate) fn map(file: &File) -> Option<Mmap> {
+    // SAFETY: the map is only ever read, but the file can still change
+    // underneath it. This assumes that nothing truncates or rewrites the
+    // file while it's scanned. If something does, the scan may see a mix of
+    // old and new content, and once the file is truncated, reading a page
+    // past its new end raises SIGBUS, which kills the whole process instead
+    // of failing the scan of that file.
+    unsafe { Mmap::map(file) }.ok()
+}
+
+/// Wraps the file in a decoder for its compression.
+pub(crate) fn decompress<'a>(
+    file: impl BufRead + 'a,
+    compression: Option<Compression>,
+) -> io::Result<Box<dyn Read + 'a>> {
+    Ok(match compression {
+        None => Box::new(file),
+        Some(Compression::Gzip) => Box::new(MultiGzDecoder::new(file)),
+        Some(Compression::Zstd) => Box::new(zstd::Decoder::with_buffer(file)?),
+        Some(Compression::Bzip2) => Box::new(MultiBzDecoder::new(file)),
+        Some(Compression::Xz) => Box::new(XzDecoder::new_multi_decoder(file)),
+    })
+}
+
+/// Counts the bytes read from a compressed file, which the decoder hides
+pub(crate) struct Counting<R> {
+    inner: R,
+    read: Arc<AtomicU64>,
+}
+
+impl<R> Counting<R> {
+    pub(crate) fn new(inner: R, read: Arc<AtomicU64>) -> Self {
+        Self { inner, read }
+    }
+
+    pub(crate) fn get_ref(&self) -> &R {
+        &self.inner
+    }
+}
+
+impl<R: Read> Read for Counting<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.read.fetch_add(read as u64, Ordering::Relaxed);
+        Ok(read)
+    }
+}
+
+impl<R: Seek> Seek for Counting<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
@@ -0,0 +1,261 @@
+use std::{
+    fmt,
+    io::{self, BufRead},
+    ops::Range,
+    str::FromStr,
+};
+
+/// How the characters of the input are encoded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    /// Detected by the byte order mark or the first bytes of the input, and
+    /// taken as UTF-8 until an invalid sequence shows it's Windows-1252
+    #[default]
+    Auto,
+    Utf8,
+    Windows1252,
+    /// ISO 8859-1, every byte is the code point of the same number
+    Latin1,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Encoding {
+    pub const ALL: [Encoding; 6] = [
+        Encoding::Auto,
+        Encoding::Utf8,
+        Encoding::Windows1252,
+        Encoding::Latin1,
+        Encoding::Utf16Le,
+        Encoding::Utf16Be,
+    ];
+
+    /// Guesses the encoding from the start of the input.
+    ///
+    /// A byte order mark decides, otherwise text with a zero byte in every
+    /// other position is UTF-16 and anything else is UTF-8, unless it turns
+    /// out to be Windows-1252 later on, see [`Encoding::is_certain`].
+    pub(crate) fn detect(start: &[u8]) -> Self {
+        if start.starts_with(&UTF8_BOM) {
+            return Encoding::Utf8;
+        }
+        if start.starts_with(&[0xff, 0xfe]) {
+            return Encoding::Utf16Le;
+        }
+        if start.starts_with(&[0xfe, 0xff]) {
+            return Encoding::Utf16Be;
+        }
+
+        let pairs = start.chunks_exact(2).take(512);
+        let count = pairs.len();
+        let (even_zeros, odd_zeros) = pairs.fold((0, 0), |(even, odd), pair| {
+            (
+                even + usize::from(pair[0] == 0),
+                odd + usize::from(pair[1] == 0),
+            )
+        });
+        if count > 0 && odd_zeros * 2 > count && even_zeros == 0 {
+            return Encoding::Utf16Le;
+        }
+        if count > 0 && even_zeros * 2 > count && odd_zeros == 0 {
+            return Encoding::Utf16Be;
+        }
+
+        Encoding::Utf8
+    }
+
+    /// Whether the encoding [`Encoding::detect`]ed from the start of the
+    /// input is certain. UTF-8 without a byte order mark may still turn out
+    /// to be Windows-1252, which most single byte encodings resemble, at
+    /// the first invalid sequence.
+    pub(crate) fn is_certain(self, start: &[u8]) -> bool {
+        self != Encoding::Utf8 || start.starts_with(&UTF8_BOM)
+    }
+
+    /// How many bytes a character takes in the input
+    pub(crate) fn char_len(self, char: char) -> usize {
+        match self {
+            Encoding::Auto | Encoding::Utf8 => char.len_utf8(),
+            Encoding::Windows1252 | Encoding::Latin1 => 1,
+            Encoding::Utf16Le | Encoding::Utf16Be => char.len_utf16() * 2,
+        }
+    }
+
+    /// Reads the next character, `None` at the end of the input.
+    pub(crate) fn read_char(self, reader: &mut impl BufRead) -> io::Result<Option<Decoded>> {
+        match self {
+            Encoding::Auto | Encoding::Utf8 => Ok(read_utf8(reader)?.map(|read| match read {
+                Ok((char, len)) => Decoded::Char(char, len),
+                Err(bytes) => Decoded::Invalid(bytes.len()),
+            })),
+            Encoding::Windows1252 => {
+                Ok(read_byte(reader)?.map(|byte| Decoded::Char(windows_1252(byte), 1)))
+            }
+            Encoding::Latin1 => {
+                Ok(read_byte(reader)?.map(|byte| Decoded::Char(char::from(byte), 1)))
+            }
+            Encoding::Utf16Le => read_utf16(reader, u16::from_le_bytes),
+            Encoding::Utf16Be => read_utf16(reader, u16::from_be_bytes),
+        }
+    }
+}
+
+/// A character read from the input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Decoded {
+    /// A character and how many bytes it takes in the input
+    Char(char, usize),
+    /// This many bytes aren't a valid character
+    Invalid(usize),
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Encoding::Auto => "Auto",
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Windows1252 => "Windows-1252",
+            Encoding::Latin1 => "Latin-1",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+        }
+        .fmt(f)
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let normalized = name.to_lowercase().replace(['-', '_'], "");
+        match normalized.as_str() {
+            "auto" => Ok(Encoding::Auto),
+            "utf8" => Ok(Encoding::Utf8),
+            "windows1252" | "cp1252" => Ok(Encoding::Windows1252),
+            "latin1" | "iso88591" => Ok(Encoding::Latin1),
+            "utf16le" | "utf16" => Ok(Encoding::Utf16Le),
+            "utf16be" => Ok(Encoding::Utf16Be),
+            _ => Err(format!("Unknown encoding \"{}\"", name)),
+        }
+    }
+}
+
+fn read_byte(reader: &mut impl BufRead) -> io::Result<Option<u8>> {
+    let byte = reader.fill_buf()?.first().copied();
+    if byte.is_some() {
+        reader.consume(1);
+    }
+    Ok(byte)
+}
+
+/// A UTF-8 character and how many bytes it takes, or the bytes of an
+/// invalid sequence
+pub(crate) type Utf8 = Result<(char, usize), Vec<u8>>;
+
+/// Reads the next UTF-8 character, `None` at the end of the input.
+pub(crate) fn read_utf8(reader: &mut impl BufRead) -> io::Result<Option<Utf8>> {
+    let Some(first) = read_byte(reader)? else {
+        return Ok(None);
+    };
+
+    // check how many bytes are needed for the character
+    let Some(len) = utf8_char_len(first) else {
+        return Ok(Some(Err(vec![first])));
+    };
+
+    let mut bytes = [first, 0, 0, 0];
+    for (index, continuation) in bytes.iter_mut().enumerate().take(len).skip(1) {
+        match reader.fill_buf()?.first() {
+            Some(&byte) if byte & 0b1100_0000 == 0b1000_0000 => {
+                *continuation = byte;
+                reader.consume(1);
+            }
+            // the sequence is cut short, the next byte may start a valid one
+            _ => return Ok(Some(Err(bytes[..index].to_vec()))),
+        }
+    }
+
+    // overlong encodings and surrogates are invalid as well
+    Ok(Some(
+        match std::str::from_utf8(&bytes[..len]).map(|str| str.chars().next()) {
+            Ok(Some(char)) => Ok((char, len)),
+            _ => Err(bytes[..len].to_vec()),
+        },
+    ))
+}
+
+fn utf8_char_len(first: u8) -> Option<usize> {
+    if first & 0b1000_0000 == 0 {
+        Some(1) // 0xxxxxxx
+    } else if first & 0b1110_0000 == 0b1100_0000 {
+        Some(2) // 110xxxxx
+    } else if first & 0b1111_0000 == 0b1110_0000 {
+        Some(3) // 1110xxxx
+    } else if first & 0b1111_1000 == 0b1111_0000 {
+        Some(4) // 11110xxx
+    } else {
+        None // continuation byte or invalid leading byte
+    }
+}
+
+fn read_utf16(reader: &mut impl BufRead, unit: fn([u8; 2]) -> u16) -> io::Result<Option<Decoded>> {
+    let Some(first) = read_byte(reader)? else {
+        return Ok(None);
+    };
+    let Some(second) = read_byte(reader)? else {
+        // a single byte left at the end
+        return Ok(Some(Decoded::Invalid(1)));
+    };
+
+    let high = unit([first, second]);
+    if !HIGH_SURROGATES.contains(&high) {
+        // anything but a lone low surrogate is a character of its own
+        return Ok(Some(match char::from_u32(u32::from(high)) {
+            Some(char) => Decoded::Char(char, 2),
+            None => Decoded::Invalid(2),
+        }));
+    }
+
+    // a high surrogate, followed by the low one
+    let next = reader.fill_buf()?;
+    let low = if next.len() >= 2 {
+        let low = unit([next[0], next[1]]);
+        if !LOW_SURROGATES.contains(&low) {
+            // the next unit may be a valid character
+            return Ok(Some(Decoded::Invalid(2)));
+        }
+        reader.consume(2);
+        low
+    } else {
+        // the buffer ends in between, so the next unit is consumed either way
+        match (read_byte(reader)?, read_byte(reader)?) {
+            (Some(first), Some(second)) => unit([first, second]),
+            (Some(_), None) => return Ok(Some(Decoded::Invalid(3))),
+            _ => return Ok(Some(Decoded::Invalid(2))),
+        }
+    };
+
+    Ok(Some(match char::decode_utf16([high, low]).next() {
+        Some(Ok(char)) => Decoded::Char(char, 4),
+        _ => Decoded::Invalid(4),
+    }))
+}
+
+const UTF8_BOM: [u8; 3] = [0xef, 0xbb, 0xbf];
+
+const HIGH_SURROGATES: Range<u16> = 0xd800..0xdc00;
+const LOW_SURROGATES: Range<u16> = 0xdc00..0xe000;
+
+/// The characters Windows-1252 puts where Latin-1 has control characters,
+/// unassigned bytes are mapped like in Latin-1
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8d}', 'Ž', '\u{8f}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9d}', 'ž', 'Ÿ',
+];
+
+pub(crate) fn windows_1252(byte: u8) -> char {
+    match byte {
+        0x80..=0x9f => WINDOWS_1252_HIGH[usize::from(byte - 0x80)],
+        _ => char::from(byte),
+    }
+}
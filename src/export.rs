@@ -0,0 +1,40 @@
+use std::borrow::Cow;
+
+use crate::scanner::Occurence;
+
+/// The CSV header matching the lines written by [`row`]
+pub const HEADER: &str = "File,Byte offset,Line,Char offset in line,Byte offset in line,Record,Column,Column name,Term,Match length,Matched,Distance";
+
+/// Formats an occurence as a CSV line, without the line break
+pub fn row(occurence: &Occurence, search_terms: &[String]) -> String {
+    format!(
+        "{},{},{},{},{},{},{},{},{},{},{},{}",
+        escape(
+            &occurence
+                .path
+                .as_deref()
+                .map(|path| path.to_string_lossy())
+                .unwrap_or_default()
+        ),
+        occurence.total_byte_offset,
+        occurence.line_number,
+        occurence.line_character_offset,
+        occurence.line_byte_offset,
+        occurence.record_number,
+        occurence.column_number,
+        escape(occurence.column_name.as_deref().unwrap_or_default()),
+        escape(&search_terms[occurence.term]),
+        occurence.match_length,
+        escape(&occurence.matched),
+        occurence.distance
+    )
+}
+
+/// Quotes a CSV field if it contains characters with a special meaning
+pub fn escape(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use walkdir::WalkDir;
+
+use crate::scanner::ScanError;
+
+/// The members of archives scanned unless a pattern includes others
+const TEXT_MEMBERS: [&str; 3] = ["*.csv", "*.tsv", "*.txt"];
+
+/// Decides which files below a directory are scanned.
+///
+/// Patterns are globs relative to that directory, like `*.csv` or
+/// `exports/**/2024-*.csv`. Patterns starting with `!` exclude files and
+/// whole directories instead. A pattern without a `/` matches the name in
+/// any directory, like in a `.gitignore`.
+#[derive(Debug, Clone)]
+pub(crate) struct FileFilter {
+    include: GlobSet,
+    exclude: GlobSet,
+}
+
+impl FileFilter {
+    pub(crate) fn new(patterns: &[String]) -> Result<Self, ScanError> {
+        let mut include = GlobSetBuilder::new();
+        let mut exclude = GlobSetBuilder::new();
+
+        for pattern in patterns {
+            match pattern.strip_prefix('!') {
+                Some(pattern) => exclude.add(glob(pattern)?),
+                None => include.add(glob(pattern)?),
+            };
+        }
+
+        let build = |builder: GlobSetBuilder| {
+            builder.build().map_err(|err| ScanError::InvalidPattern {
+                term: patterns.join(" "),
+                message: err.to_string(),
+            })
+        };
+
+        Ok(Self {
+            include: build(include)?,
+            exclude: build(exclude)?,
+        })
+    }
+
+    /// A filter for the members of archives, which only includes text files
+    /// unless the patterns include something.
+    pub(crate) fn members(patterns: &[String]) -> Result<Self, ScanError> {
+        if patterns.iter().any(|pattern| !pattern.starts_with('!')) {
+            return Self::new(patterns);
+        }
+        let defaults = TEXT_MEMBERS.iter().map(|pattern| pattern.to_string());
+        Self::new(&defaults.chain(patterns.iter().cloned()).collect::<Vec<_>>())
+    }
+
+    /// Whether a file at this path relative to the directory is scanned
+    pub(crate) fn matches(&self, path: &Path) -> bool {
+        (self.include.is_empty() || self.include.is_match(path))
+            && !path
+                .ancestors()
+                .any(|ancestor| self.exclude.is_match(ancestor))
+    }
+
+    /// Every file to scan below `root`, or `root` itself if it's a file.
+    pub(crate) fn files<'a>(
+        &'a self,
+        root: &'a Path,
+    ) -> impl Iterator<Item = Result<PathBuf, ScanError>> + 'a {
+        WalkDir::new(root)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(move |entry| {
+                // the root was chosen explicitly
+                entry.depth() == 0 || !self.exclude.is_match(relative(root, entry.path()))
+            })
+            .filter_map(move |entry| match entry {
+                Ok(entry) if !entry.file_type().is_file() => None,
+                Ok(entry) => {
+                    let included = entry.depth() == 0
+                        || self.include.is_empty()
+                        || self.include.is_match(relative(root, entry.path()));
+                    included.then(|| Ok(entry.into_path()))
+                }
+                Err(err) => {
+                    let path = err.path().unwrap_or(root).to_path_buf();
+                    Some(Err(ScanError::in_file(path, io::Error::from(err))))
+                }
+            })
+    }
+}
+
+fn glob(pattern: &str) -> Result<Glob, ScanError> {
+    let relative = pattern.trim_start_matches("./");
+    let glob = if relative.contains('/') {
+        relative.to_string()
+    } else {
+        format!("**/{}", relative)
+    };
+
+    GlobBuilder::new(&glob)
+        .literal_separator(true)
+        .build()
+        .map_err(|err| ScanError::InvalidPattern {
+            term: pattern.to_string(),
+            message: err.kind().to_string(),
+        })
+}
+
+fn relative<'a>(root: &Path, path: &'a Path) -> &'a Path {
+    path.strip_prefix(root).unwrap_or(path)
+}
@@ -0,0 +1,13 @@
+mod archive;
+mod date;
+pub mod decompress;
+pub mod encoding;
+pub mod export;
+mod files;
+mod matcher;
+mod number;
+mod phonetic;
+mod prefilter;
+mod query;
+pub mod scanner;
+mod tokenizer;
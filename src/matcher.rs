@@ -0,0 +1,862 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    iter,
+    ops::Range,
+};
+
+use caseless::Caseless;
+use regex::{Regex, RegexBuilder};
+use unicode_normalization::char::{
+    canonical_combining_class, decompose_canonical, decompose_compatible, is_combining_mark,
+};
+
+use crate::{
+    phonetic,
+    scanner::{CaseMode, MatchScope, Normalization, Phonetic, ScanConfig, ScanError, SearchMode},
+};
+
+/// A search term found in a field
+#[derive(Debug, Clone)]
+pub(crate) struct Match {
+    pub(crate) term: usize,
+    /// Byte range of the match in the field
+    pub(crate) range: Range<usize>,
+    /// How many edits the match is away from the term, 0 unless fuzzy
+    pub(crate) distance: u32,
+}
+
+/// Finds the search terms in the fields of the input.
+///
+/// A single term is matched with the Knuth-Morris-Pratt algorithm, several
+/// terms with an Aho-Corasick automaton so the input only has to be read once.
+/// A fuzzy search computes the edit distance to every term as it goes, and a
+/// phonetic search compares the codes of whole words.
+///
+/// Terms and fields are compared after a [`Mapping`] of every character,
+/// which may turn one character into several, like "ß" into "ss", or into
+/// none at all. A match has to cover whole characters of the field.
+///
+/// Unless matches may overlap, they are taken by where they end, the longest
+/// of those ending at the same character first, and each one has to start
+/// after the last one taken. Only then are those outside of the
+/// [`MatchScope`] left out.
+#[derive(Debug, Clone)]
+pub(crate) struct Matcher {
+    search: Search,
+    // How the characters of the fields are mapped
+    mapping: Mapping,
+    scope: MatchScope,
+    trim: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Search {
+    Literal(Literal),
+    Terms(AhoCorasick),
+    Fuzzy(Fuzzy),
+    Regex {
+        regexes: Vec<Regex>,
+        overlapping: bool,
+    },
+    Phonetic {
+        algorithm: Phonetic,
+        // The codes of every word of every term
+        terms: Vec<Vec<Vec<String>>>,
+        overlapping: bool,
+    },
+}
+
+impl Matcher {
+    pub(crate) fn new(config: &ScanConfig) -> Result<Self, ScanError> {
+        let terms = &config.search_terms;
+        let overlapping = config.overlapping;
+        let mut mapping = Mapping {
+            case: config.case,
+            normalization: config.normalization,
+            ignore_diacritics: config.ignore_diacritics,
+        };
+
+        let search = match config.mode {
+            SearchMode::Text | SearchMode::Fuzzy => {
+                let terms = terms
+                    .iter()
+                    .map(|term| mapping.map_str(term).collect::<Vec<_>>())
+                    .collect::<Vec<_>>();
+                if config.mode == SearchMode::Fuzzy {
+                    let anchored = config.scope == MatchScope::Field;
+                    Search::Fuzzy(Fuzzy::new(terms, config.max_distance, anchored))
+                } else {
+                    match terms.as_slice() {
+                        [term] => Search::Literal(Literal::new(term.clone(), overlapping)),
+                        terms => Search::Terms(AhoCorasick::new(terms, overlapping)),
+                    }
+                }
+            }
+            SearchMode::Regex | SearchMode::Wildcard => {
+                // the regex compares the case, unless it's folded, and the
+                // pattern is normalized like the fields, wildcards are
+                // matched as regexes as well
+                let case = mapping.case;
+                mapping.case = match case {
+                    CaseMode::Exact | CaseMode::Lowercase => CaseMode::Exact,
+                    CaseMode::Fold => CaseMode::Fold,
+                };
+                let pattern_mapping = Mapping {
+                    case: CaseMode::Exact,
+                    ..mapping
+                };
+
+                terms
+                    .iter()
+                    .map(|term| {
+                        let pattern = match config.mode {
+                            SearchMode::Wildcard => wildcard_pattern(term, pattern_mapping),
+                            _ => pattern_mapping.map_str(term).collect(),
+                        };
+                        RegexBuilder::new(&pattern)
+                            .case_insensitive(case != CaseMode::Exact)
+                            .build()
+                            .map_err(|err| ScanError::InvalidPattern {
+                                term: term.clone(),
+                                message: err.to_string(),
+                            })
+                    })
+                    .collect::<Result<_, _>>()
+                    .map(|regexes| Search::Regex {
+                        regexes,
+                        overlapping,
+                    })?
+            }
+            // the queries are matched against whole records instead
+            SearchMode::Query => Search::Terms(AhoCorasick::new(&[], overlapping)),
+            SearchMode::Phonetic => {
+                let algorithm = config.phonetic;
+                terms
+                    .iter()
+                    .map(|term| {
+                        let words = words(term)
+                            .into_iter()
+                            .map(|word| phonetic::codes(algorithm, &term[word]))
+                            .filter(|codes| !codes.is_empty())
+                            .collect::<Vec<_>>();
+                        if words.is_empty() {
+                            Err(ScanError::Unpronounceable(term.clone()))
+                        } else {
+                            Ok(words)
+                        }
+                    })
+                    .collect::<Result<_, _>>()
+                    .map(|terms| Search::Phonetic {
+                        algorithm,
+                        terms,
+                        overlapping,
+                    })?
+            }
+        };
+
+        Ok(Self {
+            search,
+            mapping,
+            scope: config.scope,
+            trim: config.trim,
+        })
+    }
+
+    /// Calls `on_match` for every search term found in the field, ordered by
+    /// where the matches end.
+    pub(crate) fn find(&mut self, field: &str, mut on_match: impl FnMut(Match)) {
+        let mapping = self.mapping;
+        let scope = self.scope;
+        // the value without the whitespace around it, and where it starts
+        let (field, offset) = if scope == MatchScope::Field && self.trim {
+            let trimmed = field.trim_start();
+            (trimmed.trim_end(), field.len() - trimmed.len())
+        } else {
+            (field, 0)
+        };
+        let on_match = |mut found: Match| {
+            if in_scope(field, &found.range, scope) {
+                found.range.start += offset;
+                found.range.end += offset;
+                on_match(found);
+            }
+        };
+        match &mut self.search {
+            Search::Literal(literal) => {
+                literal.reset();
+                find_chars(
+                    field,
+                    mapping,
+                    |char, found| literal.push(char, found),
+                    on_match,
+                );
+            }
+            Search::Terms(terms) => {
+                terms.reset();
+                find_chars(
+                    field,
+                    mapping,
+                    |char, found| terms.push(char, found),
+                    on_match,
+                );
+            }
+            Search::Fuzzy(fuzzy) => {
+                fuzzy.reset();
+                let mut candidates = Vec::new();
+                find_chars(
+                    field,
+                    mapping,
+                    |char, found| fuzzy.push(char, found),
+                    |found| {
+                        if in_scope(field, &found.range, scope) {
+                            candidates.push(found);
+                        }
+                    },
+                );
+                closest(candidates).into_iter().for_each(on_match);
+            }
+            Search::Regex {
+                regexes,
+                overlapping,
+            } => {
+                let mut matches = Vec::new();
+                if mapping.is_identity() {
+                    for (term, regex) in regexes.iter().enumerate() {
+                        find_regex(regex, field, |range| {
+                            matches.push(Match {
+                                term,
+                                range,
+                                distance: 0,
+                            })
+                        });
+                    }
+                } else {
+                    let (mapped, origins) = map_field(field, mapping);
+                    for (term, regex) in regexes.iter().enumerate() {
+                        find_regex(regex, &mapped, |range| {
+                            if let Some(range) = origin(&mapped, &origins, range, mapping) {
+                                matches.push(Match {
+                                    term,
+                                    range,
+                                    distance: 0,
+                                });
+                            }
+                        });
+                    }
+                }
+                take_in_order(matches, *overlapping, on_match);
+            }
+            Search::Phonetic {
+                algorithm,
+                terms,
+                overlapping,
+            } => {
+                // the words of the field which have a code at all
+                let words = words(field)
+                    .into_iter()
+                    .map(|word| {
+                        let codes = phonetic::codes(*algorithm, &field[word.clone()]);
+                        (word, codes)
+                    })
+                    .filter(|(_, codes)| !codes.is_empty())
+                    .collect::<Vec<_>>();
+
+                let mut matches = Vec::new();
+                for (term, term_words) in terms.iter().enumerate() {
+                    for window in words.windows(term_words.len()) {
+                        let sounds_alike =
+                            window
+                                .iter()
+                                .zip(term_words)
+                                .all(|((_, codes), term_codes)| {
+                                    codes.iter().any(|code| term_codes.contains(code))
+                                });
+                        if sounds_alike {
+                            matches.push(Match {
+                                term,
+                                range: window[0].0.start..window[window.len() - 1].0.end,
+                                distance: 0,
+                            });
+                        }
+                    }
+                }
+                take_in_order(matches, *overlapping, on_match);
+            }
+        }
+    }
+}
+
+/// Calls `on_match` with the matches ordered by where they end, the longest
+/// first like the streaming matchers, and unless they may overlap only with
+/// those starting after the last one taken ended.
+fn take_in_order(mut matches: Vec<Match>, overlapping: bool, mut on_match: impl FnMut(Match)) {
+    matches.sort_by_key(|found| (found.range.end, found.range.start));
+
+    let mut last_end = 0;
+    for found in matches {
+        if overlapping || found.range.start >= last_end {
+            last_end = found.range.end;
+            on_match(found);
+        }
+    }
+}
+
+/// How the characters of terms and fields are mapped before comparing them
+#[derive(Debug, Clone, Copy)]
+struct Mapping {
+    case: CaseMode,
+    normalization: Normalization,
+    ignore_diacritics: bool,
+}
+
+impl Mapping {
+    /// Whether every character is compared as itself
+    fn is_identity(self) -> bool {
+        self.case == CaseMode::Exact && !self.decomposes()
+    }
+
+    /// Whether characters are compared in decomposed form, which finds the
+    /// same matches as composing them would
+    fn decomposes(self) -> bool {
+        self.normalization != Normalization::None || self.ignore_diacritics
+    }
+
+    /// The character an ASCII character is compared as, which is always a
+    /// single one.
+    fn map_ascii(self, char: char) -> char {
+        match self.case {
+            CaseMode::Exact => char,
+            CaseMode::Lowercase | CaseMode::Fold => char.to_ascii_lowercase(),
+        }
+    }
+
+    /// Appends the characters `char` is compared as.
+    fn map(self, char: char, chars: &mut Vec<char>) {
+        if char.is_ascii() {
+            // the common case, without looking up any tables
+            chars.push(self.map_ascii(char));
+            return;
+        }
+        if !self.decomposes() {
+            map_case(self.case, char, chars);
+            return;
+        }
+
+        // the case mapping of a decomposed character may need to be
+        // decomposed again, like the iota subscript
+        let mut decomposed = Vec::new();
+        self.decompose(char, |char| decomposed.push(char));
+        let mut cased = Vec::new();
+        for char in decomposed {
+            map_case(self.case, char, &mut cased);
+        }
+        for char in cased {
+            self.decompose(char, |char| {
+                if !(self.ignore_diacritics && is_combining(char)) {
+                    chars.push(char);
+                }
+            });
+        }
+    }
+
+    fn decompose(self, char: char, emit: impl FnMut(char)) {
+        match self.normalization {
+            Normalization::Nfkc => decompose_compatible(char, emit),
+            Normalization::None | Normalization::Nfc => decompose_canonical(char, emit),
+        }
+    }
+
+    /// The characters `text` is compared as.
+    fn map_str(self, text: &str) -> impl Iterator<Item = char> {
+        let mut chars = Vec::with_capacity(text.len());
+        for char in text.chars() {
+            self.map(char, &mut chars);
+        }
+        chars.into_iter()
+    }
+}
+
+/// Whether a match at `range` spans as much of `field` as the scope asks for.
+fn in_scope(field: &str, range: &Range<usize>, scope: MatchScope) -> bool {
+    match scope {
+        MatchScope::Anywhere => true,
+        MatchScope::Word => {
+            let before = field[..range.start].chars().next_back();
+            let after = field[range.end..].chars().next();
+            !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+        }
+        MatchScope::Field => *range == (0..field.len()),
+    }
+}
+
+/// Whether `char` may be part of a word, including the diacritics of its
+/// letters
+fn is_word_char(char: char) -> bool {
+    char.is_alphanumeric() || char == '_' || is_combining_mark(char)
+}
+
+/// The byte ranges of the runs of word characters in `text`
+fn words(text: &str) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (index, char) in text.char_indices() {
+        match (is_word_char(char), start) {
+            (true, None) => start = Some(index),
+            (false, Some(word_start)) => {
+                words.push(word_start..index);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(start) = start {
+        words.push(start..text.len());
+    }
+    words
+}
+
+/// Appends the characters `char` is compared as in the case mode.
+fn map_case(case: CaseMode, char: char, chars: &mut Vec<char>) {
+    match case {
+        CaseMode::Exact => chars.push(char),
+        CaseMode::Lowercase => chars.extend(char.to_lowercase()),
+        CaseMode::Fold => chars.extend(iter::once(char).default_case_fold()),
+    }
+}
+
+/// Whether `char` combines with the character before it, like most
+/// diacritics do once decomposed
+fn is_combining(char: char) -> bool {
+    canonical_combining_class(char) != 0
+}
+
+/// The regular expression matching the same as the wildcard `pattern`, with
+/// the characters in between mapped like the fields.
+///
+/// `*` takes as many characters as it can and `?` a single character along
+/// with any diacritics combined with it, so the leftmost-first matches of
+/// the regex are the leftmost-longest ones of the wildcard.
+fn wildcard_pattern(pattern: &str, mapping: Mapping) -> String {
+    let escape = |literal: &str| regex::escape(&mapping.map_str(literal).collect::<String>());
+    let mut regex = String::from("(?s)");
+    let mut literal = String::new();
+    for char in pattern.chars() {
+        let wildcard = match char {
+            '*' => ".*",
+            '?' => r"\P{M}\p{M}*",
+            _ => {
+                literal.push(char);
+                continue;
+            }
+        };
+        regex.push_str(&escape(&literal));
+        regex.push_str(wildcard);
+        literal.clear();
+    }
+    regex.push_str(&escape(&literal));
+    regex
+}
+
+/// Calls `on_match` with the leftmost-first matches of `regex` in `field`
+/// which aren't empty, none of them overlapping.
+fn find_regex(regex: &Regex, field: &str, mut on_match: impl FnMut(Range<usize>)) {
+    for found in regex.find_iter(field) {
+        if !found.is_empty() {
+            on_match(found.range());
+        }
+    }
+}
+
+/// The mapped `field`, and the byte range of the character in `field` every
+/// byte of it came from.
+fn map_field(field: &str, mapping: Mapping) -> (String, Vec<Range<usize>>) {
+    let mut mapped = String::with_capacity(field.len());
+    let mut origins = Vec::with_capacity(field.len());
+    let mut chars = Vec::new();
+
+    for (index, char) in field.char_indices() {
+        chars.clear();
+        mapping.map(char, &mut chars);
+        for &mapped_char in &chars {
+            mapped.push(mapped_char);
+            for _ in 0..mapped_char.len_utf8() {
+                origins.push(index..index + char.len_utf8());
+            }
+        }
+    }
+
+    (mapped, origins)
+}
+
+/// The range in the field a match in the mapped field covers, `None` unless
+/// it starts and ends with whole characters of the field, and no diacritic
+/// follows that would belong to the last one.
+fn origin(
+    mapped: &str,
+    origins: &[Range<usize>],
+    range: Range<usize>,
+    mapping: Mapping,
+) -> Option<Range<usize>> {
+    let first = &origins[range.start];
+    let last = &origins[range.end - 1];
+    let starts_char = range.start == 0 || origins[range.start - 1] != *first;
+    let ends_char = origins.get(range.end) != Some(last);
+    let combined = mapping.decomposes() && mapped[range.end..].starts_with(is_combining);
+    (starts_char && ends_char && !combined).then_some(first.start..last.end)
+}
+
+/// Feeds the mapped characters of `field` to a streaming matcher, which
+/// reports the index, the length in mapped characters and the distance of
+/// every term ending at a character.
+fn find_chars(
+    field: &str,
+    mapping: Mapping,
+    mut push: impl FnMut(char, &mut dyn FnMut(usize, usize, u32)),
+    mut on_match: impl FnMut(Match),
+) {
+    if field.is_ascii() {
+        // every character is mapped to one, and none is a diacritic
+        for (index, byte) in field.bytes().enumerate() {
+            push(
+                mapping.map_ascii(byte.into()),
+                &mut |term, length, distance| {
+                    on_match(Match {
+                        term,
+                        range: index + 1 - length..index + 1,
+                        distance,
+                    });
+                },
+            );
+        }
+        return;
+    }
+
+    // Byte index of the character every mapped character came from, and
+    // whether it was the first one mapped from it
+    let mut origins = Vec::with_capacity(field.len());
+    let mut chars = Vec::new();
+    // Matches in decomposed characters are only reported once the next
+    // character turns out not to be a diacritic of their last one
+    let mut pending = Vec::new();
+
+    for (index, char) in field.char_indices() {
+        let end = index + char.len_utf8();
+        chars.clear();
+        mapping.map(char, &mut chars);
+
+        if let Some(&first) = chars.first() {
+            if is_combining(first) {
+                pending.clear();
+            } else {
+                pending.drain(..).for_each(&mut on_match);
+            }
+        }
+
+        for (mapped, &mapped_char) in chars.iter().enumerate() {
+            origins.push((index, mapped == 0));
+            let ends_char = mapped == chars.len() - 1;
+            push(mapped_char, &mut |term, length, distance| {
+                let (start, starts_char) = origins[origins.len() - length];
+                if !(starts_char && ends_char) {
+                    return;
+                }
+                let found = Match {
+                    term,
+                    range: start..end,
+                    distance,
+                };
+                if mapping.decomposes() {
+                    pending.push(found);
+                } else {
+                    on_match(found);
+                }
+            });
+        }
+    }
+
+    pending.into_iter().for_each(on_match);
+}
+
+/// Finds a single term one character at a time, falling back to the longest
+/// part of it that may still be the start of a match after a mismatch.
+#[derive(Debug, Clone)]
+pub(crate) struct Literal {
+    // The characters we're searching for
+    search_chars: Vec<char>,
+    // How long the longest proper suffix of every prefix of the term is that
+    // is a prefix as well
+    fallback: Vec<usize>,
+    // How many characters of the term end at the last character
+    matched: usize,
+    overlapping: bool,
+}
+
+impl Literal {
+    fn new(search_chars: Vec<char>, overlapping: bool) -> Self {
+        let mut fallback = vec![0; search_chars.len()];
+        let mut len = 0;
+        for index in 1..search_chars.len() {
+            while len > 0 && search_chars[index] != search_chars[len] {
+                len = fallback[len - 1];
+            }
+            if search_chars[index] == search_chars[len] {
+                len += 1;
+            }
+            fallback[index] = len;
+        }
+
+        Self {
+            search_chars,
+            fallback,
+            matched: 0,
+            overlapping,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.matched = 0;
+    }
+
+    fn push(&mut self, char: char, on_match: &mut dyn FnMut(usize, usize, u32)) {
+        if self.search_chars.is_empty() {
+            return;
+        }
+
+        while self.matched > 0 && char != self.search_chars[self.matched] {
+            self.matched = self.fallback[self.matched - 1];
+        }
+        if char == self.search_chars[self.matched] {
+            self.matched += 1;
+        }
+
+        if self.matched == self.search_chars.len() {
+            on_match(0, self.matched, 0);
+            self.matched = if self.overlapping {
+                self.fallback[self.matched - 1]
+            } else {
+                0
+            };
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct AhoCorasick {
+    nodes: Vec<Node>,
+    // The node of the longest term prefix ending at the last character
+    state: usize,
+    overlapping: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Node {
+    transitions: HashMap<char, usize>,
+    // Node of the longest proper suffix that is also a term prefix
+    fail: usize,
+    // The terms ending at this node, several if the same term was given
+    // more than once
+    terms: Vec<usize>,
+    // How many characters deep this node is in the trie
+    depth: usize,
+    // Next node on the fail chain which ends a term
+    output: Option<usize>,
+}
+
+impl AhoCorasick {
+    const ROOT: usize = 0;
+
+    fn new(terms: &[Vec<char>], overlapping: bool) -> Self {
+        let mut nodes = vec![Node::default()];
+
+        for (index, term) in terms.iter().enumerate() {
+            if term.is_empty() {
+                continue;
+            }
+
+            let mut node = Self::ROOT;
+            for &char in term {
+                node = match nodes[node].transitions.get(&char) {
+                    Some(&next) => next,
+                    None => {
+                        nodes.push(Node {
+                            depth: nodes[node].depth + 1,
+                            ..Node::default()
+                        });
+                        let next = nodes.len() - 1;
+                        nodes[node].transitions.insert(char, next);
+                        next
+                    }
+                };
+            }
+            nodes[node].terms.push(index);
+        }
+
+        // breadth first, so the fail links of shorter prefixes are known
+        let mut queue = VecDeque::from([Self::ROOT]);
+        while let Some(parent) = queue.pop_front() {
+            let children = nodes[parent]
+                .transitions
+                .iter()
+                .map(|(&char, &child)| (char, child))
+                .collect::<Vec<_>>();
+
+            for (char, child) in children {
+                let fail = if parent == Self::ROOT {
+                    Self::ROOT
+                } else {
+                    let mut fail = nodes[parent].fail;
+                    loop {
+                        if let Some(&next) = nodes[fail].transitions.get(&char) {
+                            break next;
+                        }
+                        if fail == Self::ROOT {
+                            break Self::ROOT;
+                        }
+                        fail = nodes[fail].fail;
+                    }
+                };
+
+                nodes[child].fail = fail;
+                nodes[child].output = if !nodes[fail].terms.is_empty() {
+                    Some(fail)
+                } else {
+                    nodes[fail].output
+                };
+                queue.push_back(child);
+            }
+        }
+
+        Self {
+            nodes,
+            state: Self::ROOT,
+            overlapping,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.state = Self::ROOT;
+    }
+
+    fn push(&mut self, char: char, on_match: &mut dyn FnMut(usize, usize, u32)) {
+        loop {
+            if let Some(&next) = self.nodes[self.state].transitions.get(&char) {
+                self.state = next;
+                break;
+            }
+            if self.state == Self::ROOT {
+                break;
+            }
+            self.state = self.nodes[self.state].fail;
+        }
+
+        // the longest term ending here comes first
+        let mut node = Some(self.state);
+        while let Some(current) = node {
+            let terms = &self.nodes[current].terms;
+            if !terms.is_empty() {
+                for &term in terms {
+                    on_match(term, self.nodes[current].depth, 0);
+                }
+                if !self.overlapping {
+                    // the next match starts after this one
+                    self.state = Self::ROOT;
+                    return;
+                }
+            }
+            node = self.nodes[current].output;
+        }
+    }
+}
+
+/// Finds every term within an edit distance in a field one character at a
+/// time, like Sellers' algorithm.
+///
+/// For every prefix of a term it keeps the Levenshtein distance to the
+/// closest part of the field ending at the last character, and where that
+/// starts, so a match can start anywhere without any cost.
+#[derive(Debug, Clone)]
+pub(crate) struct Fuzzy {
+    terms: Vec<Vec<char>>,
+    max_distance: u32,
+    // Whether matches have to start at the start of the field
+    anchored: bool,
+    // The distance and start of every prefix of every term
+    columns: Vec<Vec<(u32, usize)>>,
+    // How many characters have been pushed
+    pushed: usize,
+}
+
+impl Fuzzy {
+    fn new(terms: Vec<Vec<char>>, max_distance: u32, anchored: bool) -> Self {
+        let mut fuzzy = Self {
+            columns: terms
+                .iter()
+                .map(|term| Vec::with_capacity(term.len() + 1))
+                .collect(),
+            terms,
+            max_distance,
+            anchored,
+            pushed: 0,
+        };
+        fuzzy.reset();
+        fuzzy
+    }
+
+    fn reset(&mut self) {
+        self.pushed = 0;
+        for (term, column) in self.terms.iter().zip(&mut self.columns) {
+            column.clear();
+            // nothing of the field, so every character of a prefix is missing
+            column.extend((0..=term.len()).map(|len| (len as u32, 0)));
+        }
+    }
+
+    fn push(&mut self, char: char, on_match: &mut dyn FnMut(usize, usize, u32)) {
+        self.pushed += 1;
+
+        for (index, (term, column)) in self.terms.iter().zip(&mut self.columns).enumerate() {
+            let mut diagonal = column[0];
+            column[0] = if self.anchored {
+                // the characters before have to be left out
+                (self.pushed as u32, 0)
+            } else {
+                (0, self.pushed)
+            };
+
+            for len in 1..=term.len() {
+                let substituted = (diagonal.0 + u32::from(term[len - 1] != char), diagonal.1);
+                let inserted = (column[len].0 + 1, column[len].1);
+                let deleted = (column[len - 1].0 + 1, column[len - 1].1);
+                diagonal = column[len];
+                column[len] = [substituted, inserted, deleted]
+                    .into_iter()
+                    .min_by_key(|(distance, _)| *distance)
+                    .unwrap();
+            }
+
+            let (distance, start) = column[term.len()];
+            if distance <= self.max_distance && start < self.pushed {
+                on_match(index, self.pushed - start, distance);
+            }
+        }
+    }
+}
+
+/// The closest of the matches that overlap each other, ordered by where they
+/// end.
+fn closest(mut matches: Vec<Match>) -> Vec<Match> {
+    matches.sort_by_key(|found| (found.distance, found.range.start, found.range.end));
+
+    let mut taken = Vec::<Match>::new();
+    for found in matches {
+        let overlaps = taken.iter().any(|taken| {
+            found.range.start < taken.range.end && taken.range.start < found.range.end
+        });
+        if !overlaps {
+            taken.push(found);
+        }
+    }
+
+    taken.sort_by_key(|found| (found.range.end, found.range.start));
+    taken
+}
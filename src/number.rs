@@ -0,0 +1,83 @@
+use crate::scanner::DecimalSeparator;
+
+/// Characters grouping the digits of either kind of number, like the
+/// apostrophe in Swiss "1'234.56" or the space in French "1 234,56"
+const GROUP_SEPARATORS: [char; 4] = ['\'', ' ', '\u{a0}', '\u{202f}'];
+
+/// Parses a number written with the given decimal separator, and optionally
+/// with the digits before it grouped by threes, like "1,234.56" or
+/// "1.234,56", `None` if it isn't one.
+///
+/// Whitespace around the number is ignored. The same group separator has to
+/// be used throughout, so "1,234.567,8" isn't a number.
+pub(crate) fn parse(text: &str, separator: DecimalSeparator) -> Option<f64> {
+    let (point, group) = match separator {
+        DecimalSeparator::Point => ('.', ','),
+        DecimalSeparator::Comma => (',', '.'),
+    };
+    let text = text.trim();
+    let (negative, text) = match text.strip_prefix(['-', '\u{2212}']) {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let (mantissa, exponent) = match text.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, Some(exponent)),
+        None => (text, None),
+    };
+    let (integer, fraction) = mantissa.split_once(point).unwrap_or((mantissa, ""));
+
+    let mut number = ungroup(integer, group)?;
+    if number.is_empty() && fraction.is_empty()
+        || !fraction.chars().all(|char| char.is_ascii_digit())
+    {
+        return None;
+    }
+    if negative {
+        number.insert(0, '-');
+    }
+    number.push('.');
+    number.push_str(fraction);
+
+    if let Some(exponent) = exponent {
+        let digits = exponent.strip_prefix(['-', '+']).unwrap_or(exponent);
+        if digits.is_empty() || !digits.chars().all(|char| char.is_ascii_digit()) {
+            return None;
+        }
+        number.push('e');
+        number.push_str(exponent);
+    }
+
+    number.parse().ok()
+}
+
+/// The digits before the decimal separator without the group separators,
+/// `None` unless every group but the first has three digits.
+fn ungroup(integer: &str, group: char) -> Option<String> {
+    let Some(separator) = integer
+        .chars()
+        .find(|char| *char == group || GROUP_SEPARATORS.contains(char))
+    else {
+        return integer
+            .chars()
+            .all(|char| char.is_ascii_digit())
+            .then(|| integer.to_string());
+    };
+
+    let mut groups = integer.split(separator);
+    let first = groups.next()?;
+    if first.is_empty() || first.len() > 3 {
+        return None;
+    }
+    let mut digits = first.to_string();
+    for group in groups {
+        if group.len() != 3 {
+            return None;
+        }
+        digits.push_str(group);
+    }
+
+    digits
+        .chars()
+        .all(|char| char.is_ascii_digit())
+        .then_some(digits)
+}
@@ -0,0 +1,155 @@
+use memchr::{memchr, memchr_iter, memchr2, memchr2_iter, memchr3, memrchr};
+
+use crate::scanner::{CaseMode, Normalization, ScanConfig, SearchMode};
+
+/// Bytes ordered from the most to the least common in typical CSV files.
+/// Anything not listed is taken to be rarer still.
+const COMMON_BYTES: &[u8] = b" ,;\"0123456789eatonsrlhdcumgpfbywvxjqz-./:_";
+
+/// Finds the stretches of input a plain single term search can't match in,
+/// so they don't have to be split into fields at all.
+///
+/// Every match of the term contains its rarest byte, in either ASCII case
+/// unless the case has to match exactly, so whole records without that byte
+/// can be skipped with a vectorized search. Quotes are followed on the way,
+/// so a quoted field carrying over a line break doesn't end a record there.
+/// The records around a candidate are scanned as usual, which keeps the
+/// results exactly the same.
+#[derive(Debug, Clone)]
+pub(crate) struct Prefilter {
+    lower: u8,
+    upper: u8,
+    // First byte of the quote character
+    quote: Option<u8>,
+    // The separator, if both it and the quote are ASCII, so quotes can be
+    // followed byte by byte. Otherwise every quote ends a skip.
+    separator: Option<u8>,
+}
+
+impl Prefilter {
+    /// The prefilter for the configured search, `None` unless it's a single
+    /// plain term made of ASCII characters which isn't normalized, and no
+    /// records are filtered.
+    pub(crate) fn new(config: &ScanConfig) -> Option<Self> {
+        let [term] = config.search_terms.as_slice() else {
+            return None;
+        };
+        if config.mode != SearchMode::Text || term.is_empty() || !term.is_ascii() {
+            return None;
+        }
+        // filters have to see every record, and report the values they
+        // can't compare
+        if !config.filters.is_empty() {
+            return None;
+        }
+        // plenty of decomposed characters start with an ASCII letter
+        if config.normalization != Normalization::None || config.ignore_diacritics {
+            return None;
+        }
+
+        // bytes some other characters are compared as too, like 'İ' as 'i'
+        // or 'ß' as "ss", so a match doesn't need to contain them
+        let (term, mapped) = match config.case {
+            CaseMode::Exact => (term.clone(), &b""[..]),
+            CaseMode::Lowercase => (term.to_ascii_lowercase(), &b"ik"[..]),
+            CaseMode::Fold => (term.to_ascii_lowercase(), &b"afhijklnstwy"[..]),
+        };
+        let rare = term.bytes().max_by_key(|byte| {
+            if mapped.contains(byte) {
+                0
+            } else {
+                rarity(*byte)
+            }
+        })?;
+        if mapped.contains(&rare) {
+            return None;
+        }
+
+        Some(Self {
+            lower: rare,
+            upper: match config.case {
+                CaseMode::Exact => rare,
+                CaseMode::Lowercase | CaseMode::Fold => rare.to_ascii_uppercase(),
+            },
+            quote: config
+                .quote
+                .map(|quote| quote.encode_utf8(&mut [0; 4]).as_bytes()[0]),
+            separator: config
+                .quote
+                .is_none_or(|quote| quote.is_ascii())
+                .then(|| u8::try_from(config.seperator).ok())
+                .flatten()
+                .filter(u8::is_ascii),
+        })
+    }
+
+    /// How many bytes at the start of `text`, which starts a record, are
+    /// whole records without a possible match, and how many records they
+    /// are.
+    pub(crate) fn skippable(&self, text: &str) -> (usize, u64) {
+        let bytes = text.as_bytes();
+        let candidate = match (self.quote, self.separator) {
+            (None, _) | (Some(_), Some(_)) => memchr2(self.lower, self.upper, bytes),
+            (Some(quote), None) => memchr3(self.lower, self.upper, quote, bytes),
+        };
+        let end = candidate.unwrap_or(bytes.len());
+
+        match (self.quote, self.separator) {
+            (Some(quote), Some(separator)) if memchr(quote, &bytes[..end]).is_some() => {
+                records(&bytes[..end], bytes, quote, separator)
+            }
+            // up to the line break before the candidate, a record at the end
+            // of the text may go on after it, and every line is a record
+            _ => match memrchr(b'\n', &bytes[..end]) {
+                Some(newline) => (
+                    newline + 1,
+                    memchr_iter(b'\n', &bytes[..newline + 1]).count() as u64,
+                ),
+                None => (0, 0),
+            },
+        }
+    }
+}
+
+/// Where the last record ending in `span` ends and how many records end in
+/// it, following the quotes like the tokenizer: a quote at the start of a
+/// field opens it, the next one closes it unless it's doubled, even with
+/// carriage returns in between, and any other quote is just a character. `text` is what the span starts, to look past
+/// its end.
+fn records(span: &[u8], text: &[u8], quote: u8, separator: u8) -> (usize, u64) {
+    let mut end = 0;
+    let mut records = 0;
+    let mut quoted = false;
+    let mut events = memchr2_iter(b'\n', quote, span);
+
+    while let Some(index) = events.next() {
+        if span[index] == b'\n' {
+            if !quoted {
+                end = index + 1;
+                records += 1;
+            }
+        } else if quoted {
+            // carriage returns after a closing quote leave it undecided
+            match text[index + 1..].iter().find(|&&next| next != b'\r') {
+                // an escaped quote
+                Some(&next) if next == quote => {
+                    events.next();
+                }
+                Some(_) => quoted = false,
+                // whether it's doubled is only known with the next text
+                None => break,
+            }
+        } else {
+            quoted = index == 0 || span[index - 1] == b'\n' || span[index - 1] == separator;
+        }
+    }
+
+    (end, records)
+}
+
+fn rarity(byte: u8) -> usize {
+    COMMON_BYTES
+        .iter()
+        .position(|&common| common == byte)
+        .map_or(COMMON_BYTES.len() + 1, |index| index + 1)
+}
@@ -0,0 +1,431 @@
+use std::{cmp::Ordering, sync::Arc};
+
+use crate::{
+    matcher::Matcher,
+    number,
+    scanner::{DecimalSeparator, MatchScope, ScanConfig, ScanError, SearchMode},
+};
+
+/// A condition on the fields of a record, like
+/// `country = "DE" AND (status = "open" OR amount > 1000) AND NOT email ~ "@test"`.
+///
+/// Columns are named like in the header, in backticks if the name contains
+/// anything but letters, digits and characters like `_`, `-` or `.`.
+/// Values are bare words and numbers, or quoted with `"`, in which a
+/// backslash escapes a quote or another backslash.
+///
+/// - `=` and `!=` compare the whole field like a text search spanning the
+///   whole field, so the case and normalization apply
+/// - `~` and `!~` find the value in the field like a text search in the
+///   configured [`MatchScope`]
+/// - `<`, `<=`, `>` and `>=` compare numbers written with the configured
+///   [`DecimalSeparator`], fields which aren't numbers don't match
+///
+/// `NOT` binds closer than `AND`, which binds closer than `OR`, and the
+/// keywords are case insensitive. Comparisons of columns a record doesn't
+/// have are false.
+#[derive(Debug, Clone)]
+pub(crate) struct Query {
+    expr: Expr,
+    // The column names in the order they're first used
+    columns: Vec<String>,
+    decimal_separator: DecimalSeparator,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Not(Box<Expr>),
+    Compare {
+        // Index in the query's columns
+        column: usize,
+        test: Test,
+    },
+}
+
+#[derive(Debug, Clone)]
+enum Test {
+    /// The field has a match of the value, which isn't empty
+    Text(Matcher),
+    /// The field is empty, apart from whitespace if it's trimmed
+    Empty { trim: bool },
+    /// The field is a number which compares to the value in one of these
+    /// ways
+    Number(f64, &'static [Ordering]),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Equal,
+    NotEqual,
+    Contains,
+    NotContains,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Open,
+    Close,
+    Op(Op),
+    Word(String),
+    Quoted(String),
+    // A column name in backticks
+    Name(String),
+}
+
+/// What's wrong with a query, and at which character
+type SyntaxError = (usize, String);
+
+impl Query {
+    /// The queries of a query search, one for each search term, none for
+    /// any other search.
+    pub(crate) fn parse_all(config: &ScanConfig) -> Result<Vec<Self>, ScanError> {
+        if config.mode != SearchMode::Query {
+            return Ok(Vec::new());
+        }
+
+        config
+            .search_terms
+            .iter()
+            .map(|query| {
+                let parsed = Self::parse(query, config).map_err(|(position, message)| {
+                    ScanError::InvalidQuery {
+                        query: query.clone(),
+                        message: format!("{} at character {}", message, position + 1),
+                    }
+                })?;
+                // the names can't refer to anything without a header
+                match parsed.columns.first() {
+                    Some(name) if !config.has_header => Err(ScanError::UnknownColumn(name.clone())),
+                    _ => Ok(parsed),
+                }
+            })
+            .collect()
+    }
+
+    fn parse(query: &str, config: &ScanConfig) -> Result<Self, SyntaxError> {
+        let mut parser = Parser {
+            tokens: tokenize(query)?,
+            next: 0,
+            end: query.chars().count(),
+            config,
+            columns: Vec::new(),
+        };
+
+        let expr = parser.or()?;
+        if let Some((position, _)) = parser.peek() {
+            return Err((position, "Expected AND, OR or the end".to_string()));
+        }
+
+        Ok(Self {
+            expr,
+            columns: parser.columns,
+            decimal_separator: config.decimal_separator,
+        })
+    }
+
+    /// Where the columns of the query are in a record with this header.
+    pub(crate) fn resolve(&self, header: &[Arc<str>]) -> Result<Vec<usize>, ScanError> {
+        self.columns
+            .iter()
+            .map(|name| {
+                header
+                    .iter()
+                    .position(|column| column.trim() == name.trim())
+                    .ok_or_else(|| ScanError::UnknownColumn(name.clone()))
+            })
+            .collect()
+    }
+
+    /// Whether the record with these fields matches, with the columns of the
+    /// query at the indices [`Query::resolve`] found.
+    pub(crate) fn matches(&mut self, fields: &[String], columns: &[usize]) -> bool {
+        self.expr.matches(fields, columns, self.decimal_separator)
+    }
+}
+
+impl Expr {
+    fn matches(
+        &mut self,
+        fields: &[String],
+        columns: &[usize],
+        decimal_separator: DecimalSeparator,
+    ) -> bool {
+        match self {
+            Expr::And(exprs) => exprs
+                .iter_mut()
+                .all(|expr| expr.matches(fields, columns, decimal_separator)),
+            Expr::Or(exprs) => exprs
+                .iter_mut()
+                .any(|expr| expr.matches(fields, columns, decimal_separator)),
+            Expr::Not(expr) => !expr.matches(fields, columns, decimal_separator),
+            Expr::Compare { column, test } => {
+                let Some(field) = fields.get(columns[*column]) else {
+                    return false;
+                };
+                match test {
+                    Test::Text(matcher) => {
+                        let mut found = false;
+                        matcher.find(field, |_| found = true);
+                        found
+                    }
+                    Test::Empty { trim } => {
+                        if *trim {
+                            field.trim().is_empty()
+                        } else {
+                            field.is_empty()
+                        }
+                    }
+                    Test::Number(value, orderings) => number::parse(field, decimal_separator)
+                        .and_then(|number| number.partial_cmp(value))
+                        .is_some_and(|ordering| orderings.contains(&ordering)),
+                }
+            }
+        }
+    }
+}
+
+/// Splits a query into tokens along with the index of the character each
+/// starts at.
+fn tokenize(query: &str) -> Result<Vec<(usize, Token)>, SyntaxError> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().enumerate().peekable();
+
+    while let Some((position, char)) = chars.next() {
+        let mut followed_by = |next| chars.next_if(|&(_, char)| char == next).is_some();
+        let token = match char {
+            _ if char.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '=' => {
+                // "==" is taken as "=" as well
+                followed_by('=');
+                Token::Op(Op::Equal)
+            }
+            '~' => Token::Op(Op::Contains),
+            '!' if followed_by('=') => Token::Op(Op::NotEqual),
+            '!' if followed_by('~') => Token::Op(Op::NotContains),
+            '!' => return Err((position, "Expected != or !~".to_string())),
+            '<' if followed_by('=') => Token::Op(Op::LessOrEqual),
+            '<' => Token::Op(Op::Less),
+            '>' if followed_by('=') => Token::Op(Op::GreaterOrEqual),
+            '>' => Token::Op(Op::Greater),
+            '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) if let Some((_, escaped)) = chars.next() => {
+                            value.push(escaped);
+                        }
+                        Some((_, char)) => value.push(char),
+                        None => return Err((position, "Unclosed quote".to_string())),
+                    }
+                }
+                Token::Quoted(value)
+            }
+            '`' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '`')) => break,
+                        Some((_, char)) => name.push(char),
+                        None => return Err((position, "Unclosed backtick".to_string())),
+                    }
+                }
+                Token::Name(name)
+            }
+            _ => {
+                let mut word = String::from(char);
+                while let Some((_, char)) = chars.next_if(|&(_, char)| is_word_char(char)) {
+                    word.push(char);
+                }
+                Token::Word(word)
+            }
+        };
+        tokens.push((position, token));
+    }
+
+    Ok(tokens)
+}
+
+/// Whether `char` may be part of a bare word
+fn is_word_char(char: char) -> bool {
+    !char.is_whitespace() && !"()=~!<>\"`".contains(char)
+}
+
+/// Parses the tokens of a query by recursive descent, one function for every
+/// level of precedence.
+struct Parser<'a> {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+    // Where the query ends, for errors at the end
+    end: usize,
+    config: &'a ScanConfig,
+    columns: Vec<String>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<(usize, &Token)> {
+        self.tokens
+            .get(self.next)
+            .map(|(position, token)| (*position, token))
+    }
+
+    /// Where the next token starts, or the query ends
+    fn position(&self) -> usize {
+        self.peek().map_or(self.end, |(position, _)| position)
+    }
+
+    /// Takes the next token if it's the keyword, in any case.
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = matches!(
+            self.peek(),
+            Some((_, Token::Word(word))) if word.eq_ignore_ascii_case(keyword)
+        );
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Expr, SyntaxError> {
+        let mut exprs = vec![self.and()?];
+        while self.keyword("OR") {
+            exprs.push(self.and()?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            Expr::Or(exprs)
+        })
+    }
+
+    fn and(&mut self) -> Result<Expr, SyntaxError> {
+        let mut exprs = vec![self.not()?];
+        while self.keyword("AND") {
+            exprs.push(self.not()?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            Expr::And(exprs)
+        })
+    }
+
+    fn not(&mut self) -> Result<Expr, SyntaxError> {
+        if self.keyword("NOT") {
+            Ok(Expr::Not(Box::new(self.not()?)))
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, SyntaxError> {
+        let position = self.position();
+        let name = match self.peek() {
+            Some((_, Token::Open)) => {
+                self.next += 1;
+                let expr = self.or()?;
+                if self.peek().is_some_and(|(_, token)| *token == Token::Close) {
+                    self.next += 1;
+                    return Ok(expr);
+                }
+                return Err((
+                    self.position(),
+                    "Expected a closing parenthesis".to_string(),
+                ));
+            }
+            Some((_, Token::Word(word)))
+                if !["AND", "OR", "NOT"]
+                    .iter()
+                    .any(|keyword| word.eq_ignore_ascii_case(keyword)) =>
+            {
+                word.clone()
+            }
+            Some((_, Token::Name(name))) => name.clone(),
+            _ => return Err((position, "Expected a column name".to_string())),
+        };
+        self.next += 1;
+
+        let op = match self.peek() {
+            Some((_, Token::Op(op))) => *op,
+            _ => {
+                return Err((
+                    self.position(),
+                    format!("Expected an operator like = after \"{}\"", name),
+                ));
+            }
+        };
+        self.next += 1;
+
+        let position = self.position();
+        let value = match self.peek() {
+            Some((_, Token::Word(value) | Token::Quoted(value))) => value.clone(),
+            _ => return Err((position, "Expected a value".to_string())),
+        };
+        self.next += 1;
+
+        let column = match self.columns.iter().position(|column| *column == name) {
+            Some(column) => column,
+            None => {
+                self.columns.push(name);
+                self.columns.len() - 1
+            }
+        };
+        let test = self
+            .test(op, value)
+            .map_err(|message| (position, message))?;
+        let compare = Expr::Compare { column, test };
+
+        Ok(match op {
+            Op::NotEqual | Op::NotContains => Expr::Not(Box::new(compare)),
+            _ => compare,
+        })
+    }
+
+    fn test(&self, op: Op, value: String) -> Result<Test, String> {
+        let orderings: &[Ordering] = match op {
+            Op::Equal | Op::NotEqual | Op::Contains | Op::NotContains => &[],
+            Op::Less => &[Ordering::Less],
+            Op::LessOrEqual => &[Ordering::Less, Ordering::Equal],
+            Op::Greater => &[Ordering::Greater],
+            Op::GreaterOrEqual => &[Ordering::Greater, Ordering::Equal],
+        };
+        if !orderings.is_empty() {
+            return match number::parse(&value, self.config.decimal_separator) {
+                Some(number) => Ok(Test::Number(number, orderings)),
+                None => Err(format!("Expected a number instead of \"{}\"", value)),
+            };
+        }
+
+        let scope = match op {
+            Op::Equal | Op::NotEqual => MatchScope::Field,
+            _ => self.config.scope,
+        };
+
+        if value.is_empty() {
+            return match scope {
+                MatchScope::Field => Ok(Test::Empty {
+                    trim: self.config.trim,
+                }),
+                _ => Err("Every field contains an empty value".to_string()),
+            };
+        }
+
+        let search = ScanConfig {
+            search_terms: vec![value],
+            mode: SearchMode::Text,
+            scope,
+            ..self.config.clone()
+        };
+        Matcher::new(&search)
+            .map(Test::Text)
+            .map_err(|err| err.to_string())
+    }
+}
@@ -0,0 +1,1999 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    mem,
+    ops::{Bound, ControlFlow, RangeBounds},
+    path::{Path, PathBuf},
+    pin::Pin,
+    str::FromStr,
+    sync::Arc,
+    task::{Context, Poll},
+    thread,
+};
+
+use futures_core::Stream;
+use tokio::sync::mpsc;
+
+use crate::{
+    archive::Archive,
+    date,
+    decompress::{BUFFER_SIZE, Compression, Input},
+    encoding::{self, Decoded, Encoding},
+    files::FileFilter,
+    matcher::{Match, Matcher},
+    number,
+    prefilter::Prefilter,
+    query::Query,
+    tokenizer::{Token, Tokenizer},
+};
+
+mod parallel;
+
+/// How many bytes are scanned between two [`ScanUpdate`]s of a [`ScanStream`]
+const UPDATE_INTERVAL: u64 = 1024 * 1024;
+
+/// How the search terms are interpreted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SearchMode {
+    /// Plain text
+    #[default]
+    Text,
+    /// Regular expressions, matched against each field
+    Regex,
+    /// Plain text in which `*` stands for any number of characters and `?`
+    /// for a single one, like `INV-2024-*-DE`, both within a field
+    Wildcard,
+    /// Plain text with up to [`ScanConfig::max_distance`] characters
+    /// inserted, left out or replaced, so "Meier" also finds "Meyer".
+    ///
+    /// Of matches overlapping each other only the closest one is reported,
+    /// whether matches may overlap or not.
+    Fuzzy,
+    /// Words which sound like the words of a term by the
+    /// [`ScanConfig::phonetic`] algorithm, so "Meier" also finds "Mayer".
+    ///
+    /// A match spans as many words as the term has, whatever their case and
+    /// diacritics, and words without letters are left out.
+    Phonetic,
+    /// Conditions on the fields of a record, referring to the columns by
+    /// their names in the header, like
+    /// `country = "DE" AND (status = "open" OR amount > 1000)`.
+    ///
+    /// Every record matching one of them is reported as a single
+    /// [`Occurence`], the selected columns are ignored.
+    Query,
+}
+
+impl SearchMode {
+    pub const ALL: [SearchMode; 6] = [
+        SearchMode::Text,
+        SearchMode::Regex,
+        SearchMode::Wildcard,
+        SearchMode::Fuzzy,
+        SearchMode::Phonetic,
+        SearchMode::Query,
+    ];
+}
+
+impl fmt::Display for SearchMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchMode::Text => "Text",
+            SearchMode::Regex => "Regex",
+            SearchMode::Wildcard => "Wildcards",
+            SearchMode::Fuzzy => "Fuzzy",
+            SearchMode::Phonetic => "Phonetic",
+            SearchMode::Query => "Query",
+        }
+        .fmt(f)
+    }
+}
+
+/// How letters of different case are compared
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum CaseMode {
+    /// Only the very same characters match
+    Exact,
+    /// Characters are compared by all of their lowercase forms, so "İ"
+    /// matches "i" followed by a combining dot above
+    #[default]
+    Lowercase,
+    /// Unicode full case folding, so "STRASSE" matches "straße".
+    ///
+    /// Regular expressions are matched case insensitively against the folded
+    /// fields, so they have to be written in folded form, like "ss" for "ß".
+    Fold,
+}
+
+impl CaseMode {
+    pub const ALL: [CaseMode; 3] = [CaseMode::Exact, CaseMode::Lowercase, CaseMode::Fold];
+}
+
+impl fmt::Display for CaseMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaseMode::Exact => "Exact",
+            CaseMode::Lowercase => "Lowercase",
+            CaseMode::Fold => "Case folding",
+        }
+        .fmt(f)
+    }
+}
+
+impl FromStr for CaseMode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "exact" => Ok(CaseMode::Exact),
+            "lower" | "lowercase" => Ok(CaseMode::Lowercase),
+            "fold" => Ok(CaseMode::Fold),
+            _ => Err(format!("Unknown case mode \"{}\"", name)),
+        }
+    }
+}
+
+/// How much of a field a match has to span
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum MatchScope {
+    /// Matches may be anywhere in a field, like "12" in "2012"
+    #[default]
+    Anywhere,
+    /// Matches must neither be preceded nor followed by a letter, digit or
+    /// underscore, like "12" in "12-13" but not in "A1234"
+    Word,
+    /// Matches must span the whole field
+    Field,
+}
+
+impl MatchScope {
+    pub const ALL: [MatchScope; 3] = [MatchScope::Anywhere, MatchScope::Word, MatchScope::Field];
+}
+
+impl fmt::Display for MatchScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatchScope::Anywhere => "Anywhere",
+            MatchScope::Word => "Whole word",
+            MatchScope::Field => "Whole field",
+        }
+        .fmt(f)
+    }
+}
+
+/// Which Unicode normalization form terms and fields are compared in, so
+/// different encodings of the same text match each other
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Normalization {
+    /// Characters are compared as they are
+    #[default]
+    None,
+    /// Canonical equivalence, so "é" matches "e" followed by a combining
+    /// acute accent
+    Nfc,
+    /// Compatibility equivalence, so also "ﬁ" matches "fi" and "Ａ" matches
+    /// "A"
+    Nfkc,
+}
+
+impl Normalization {
+    pub const ALL: [Normalization; 3] =
+        [Normalization::None, Normalization::Nfc, Normalization::Nfkc];
+}
+
+impl fmt::Display for Normalization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Normalization::None => "None",
+            Normalization::Nfc => "NFC",
+            Normalization::Nfkc => "NFKC",
+        }
+        .fmt(f)
+    }
+}
+
+impl FromStr for Normalization {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "none" => Ok(Normalization::None),
+            "nfc" => Ok(Normalization::Nfc),
+            "nfkc" => Ok(Normalization::Nfkc),
+            _ => Err(format!("Unknown normalization \"{}\"", name)),
+        }
+    }
+}
+
+/// How words are coded by their sound in a phonetic search
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Phonetic {
+    /// American Soundex, meant for English names
+    #[default]
+    Soundex,
+    /// Double Metaphone, which also knows the pronunciation of many names of
+    /// foreign origin and may code a word in two ways
+    DoubleMetaphone,
+    /// Kölner Phonetik, meant for German names
+    Cologne,
+}
+
+impl Phonetic {
+    pub const ALL: [Phonetic; 3] = [
+        Phonetic::Soundex,
+        Phonetic::DoubleMetaphone,
+        Phonetic::Cologne,
+    ];
+}
+
+impl fmt::Display for Phonetic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Phonetic::Soundex => "Soundex",
+            Phonetic::DoubleMetaphone => "Double Metaphone",
+            Phonetic::Cologne => "Kölner Phonetik",
+        }
+        .fmt(f)
+    }
+}
+
+impl FromStr for Phonetic {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let normalized = name.to_lowercase().replace(['-', '_', ' '], "");
+        match normalized.as_str() {
+            "soundex" => Ok(Phonetic::Soundex),
+            "metaphone" | "doublemetaphone" => Ok(Phonetic::DoubleMetaphone),
+            "cologne" | "koelner" | "kölner" | "kölnerphonetik" => Ok(Phonetic::Cologne),
+            _ => Err(format!("Unknown phonetic algorithm \"{}\"", name)),
+        }
+    }
+}
+
+/// A column of the input, either by its number or the name in the header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Column {
+    /// The column number, starting at 1
+    Number(u64),
+    Name(String),
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    /// Numbers select columns by number, anything else by name.
+    fn from_str(column: &str) -> Result<Self, Self::Err> {
+        match column.parse() {
+            Ok(0) => Err(ScanError::ColumnZero.to_string()),
+            Ok(number) => Ok(Column::Number(number)),
+            Err(_) => Ok(Column::Name(column.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Column {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Column::Number(number) => number.fmt(f),
+            Column::Name(name) => name.fmt(f),
+        }
+    }
+}
+
+/// How the digits after the decimal point are separated in numbers of the
+/// input, which group the digits before it with the other character
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum DecimalSeparator {
+    /// Like "1,234.56"
+    #[default]
+    Point,
+    /// Like "1.234,56"
+    Comma,
+}
+
+impl DecimalSeparator {
+    pub const ALL: [DecimalSeparator; 2] = [DecimalSeparator::Point, DecimalSeparator::Comma];
+}
+
+impl fmt::Display for DecimalSeparator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecimalSeparator::Point => "1,234.56",
+            DecimalSeparator::Comma => "1.234,56",
+        }
+        .fmt(f)
+    }
+}
+
+impl FromStr for DecimalSeparator {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "point" | "." => Ok(DecimalSeparator::Point),
+            "comma" | "," => Ok(DecimalSeparator::Comma),
+            _ => Err(format!("Unknown decimal separator \"{}\"", name)),
+        }
+    }
+}
+
+/// A range the value in a column has to be in for a record to be searched
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    pub column: Column,
+    pub range: ValueRange,
+}
+
+/// The values a [`Filter`] lets through
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueRange {
+    /// Numbers within the bounds, written with the
+    /// [`ScanConfig::decimal_separator`]
+    Number { min: Bound<f64>, max: Bound<f64> },
+    /// Points in time within the bounds, in seconds since 1970-01-01 00:00
+    /// UTC, taking times without an offset as UTC
+    Date {
+        min: Bound<i64>,
+        max: Bound<i64>,
+        format: DateFormat,
+    },
+}
+
+impl Filter {
+    /// Parses a filter like `quantity < 0`, `price between 1000 and 5000` or
+    /// `ordered between 2024-03-01 and 2024-03-31`, whose column is given by
+    /// name or number like a [`Column`].
+    ///
+    /// The operators are `<`, `<=`, `>`, `>=`, `=` and `between`, which
+    /// includes both bounds. Numbers are written with the decimal separator
+    /// of the input, so `1.000` is a thousand with
+    /// [`DecimalSeparator::Comma`]. Anything else is taken as a date in the
+    /// format of the column or like `2024-03-01 14:30`, and a date without
+    /// a time stands for the whole day, so `= 2024-03-01` finds any time of
+    /// it.
+    pub fn parse(
+        filter: &str,
+        decimal_separator: DecimalSeparator,
+        date_format: &DateFormat,
+    ) -> Result<Self, ScanError> {
+        let invalid = |message: String| ScanError::InvalidFilter {
+            filter: filter.to_string(),
+            message,
+        };
+
+        // ASCII lowercase keeps the offsets the same
+        let lowercase = filter.to_ascii_lowercase();
+        let operator = filter.find(['<', '>', '=']);
+        let (column, op, values) = match lowercase.find(" between ") {
+            Some(between) if operator.is_none_or(|operator| between < operator) => {
+                let bounds = &filter[between + " between ".len()..];
+                let Some(and) = bounds.to_ascii_lowercase().find(" and ") else {
+                    return Err(invalid("Expected \"between <min> and <max>\"".to_string()));
+                };
+                let values = vec![&bounds[..and], &bounds[and + " and ".len()..]];
+                (&filter[..between], "between", values)
+            }
+            _ => {
+                let Some(operator) = operator else {
+                    return Err(invalid(
+                        "Expected <, <=, >, >=, = or between after the column".to_string(),
+                    ));
+                };
+                let rest = &filter[operator..];
+                let (op, value) = ["<=", ">=", "<", ">", "="]
+                    .into_iter()
+                    .find_map(|op| Some((op, rest.strip_prefix(op)?)))
+                    .expect("starts with an operator");
+                (&filter[..operator], op, vec![value])
+            }
+        };
+
+        let numbers = values
+            .iter()
+            .map(|value| number::parse(value, decimal_separator))
+            .collect::<Option<Vec<_>>>();
+        let range = match numbers {
+            // a number covers just itself
+            Some(numbers) => {
+                let (min, max) = bounds(
+                    op,
+                    numbers
+                        .into_iter()
+                        .map(|number| (Bound::Included(number), Bound::Included(number))),
+                )
+                .map_err(|(min, max)| invalid(format!("{} is greater than {}", min, max)))?;
+                ValueRange::Number { min, max }
+            }
+            None => {
+                let dates = values
+                    .iter()
+                    .map(|value| {
+                        date::parse(value, date_format)
+                            .or_else(|| date::parse(value, &DateFormat::Auto))
+                            .ok_or_else(|| {
+                                invalid(format!(
+                                    "Expected a number or a date instead of \"{}\"",
+                                    value.trim()
+                                ))
+                            })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                // a date without a time covers the whole day
+                let (min, max) = bounds(
+                    op,
+                    dates.into_iter().map(|date| {
+                        let end = match date.end() {
+                            Some(end) => Bound::Excluded(end),
+                            None => Bound::Included(date.seconds),
+                        };
+                        (Bound::Included(date.seconds), end)
+                    }),
+                )
+                .map_err(|_| invalid("The first date is after the second".to_string()))?;
+                ValueRange::Date {
+                    min,
+                    max,
+                    format: date_format.clone(),
+                }
+            }
+        };
+
+        let column = column.trim();
+        if column.is_empty() {
+            return Err(invalid("Expected a column before the operator".to_string()));
+        }
+        Ok(Self {
+            column: column.parse().map_err(invalid)?,
+            range,
+        })
+    }
+}
+
+/// The bounds of the range the operator selects, given the first and the
+/// last value each of its operands covers, or both starts if they're in the
+/// wrong order.
+fn bounds<T: Copy + PartialOrd>(
+    op: &str,
+    mut operands: impl Iterator<Item = (Bound<T>, Bound<T>)>,
+) -> Result<(Bound<T>, Bound<T>), (T, T)> {
+    let after = |end| match end {
+        Bound::Included(value) => Bound::Excluded(value),
+        Bound::Excluded(value) => Bound::Included(value),
+        Bound::Unbounded => Bound::Unbounded,
+    };
+    let (start, end) = operands.next().expect("every operator has an operand");
+
+    Ok(match op {
+        "<" => (Bound::Unbounded, after(start)),
+        "<=" => (Bound::Unbounded, end),
+        ">" => (after(end), Bound::Unbounded),
+        ">=" => (start, Bound::Unbounded),
+        "between" => {
+            let (last_start, last_end) = operands.next().expect("between has two operands");
+            if let (Bound::Included(first), Bound::Included(last)) = (start, last_start)
+                && first > last
+            {
+                return Err((first, last));
+            }
+            (start, last_end)
+        }
+        _ => (start, end),
+    })
+}
+
+impl ValueRange {
+    /// Whether the field is in the range, `None` if it isn't a value of the
+    /// right kind. Empty fields are never in it.
+    fn contains(&self, field: &str, decimal_separator: DecimalSeparator) -> Option<bool> {
+        if field.trim().is_empty() {
+            return Some(false);
+        }
+        match self {
+            ValueRange::Number { min, max } => {
+                number::parse(field, decimal_separator).map(|number| (*min, *max).contains(&number))
+            }
+            ValueRange::Date { min, max, format } => {
+                date::parse(field, format).map(|date| (*min, *max).contains(&date.seconds))
+            }
+        }
+    }
+}
+
+/// How the dates in a filtered column are written
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub enum DateFormat {
+    /// Detected for each value: ISO 8601 like `2024-03-01T14:30:00Z`,
+    /// `2024/03/01`, `01.03.2024`, `01/03/2024` with the day first,
+    /// `1 Mar 2024`, `March 1, 2024` or `Fri, 01 Mar 2024`, each optionally
+    /// followed by a time like `14:30`, `14:30:15.250` or `2:30 PM` and an
+    /// offset from UTC like `+01:00`
+    #[default]
+    Auto,
+    /// A pattern like `%m/%d/%Y %H:%M` of the specifiers
+    ///
+    /// - `%Y` and `%y` for years with four and two digits
+    /// - `%m` and `%d` for month and day, `%b` for the English name of the
+    ///   month or its first three letters and `%a` likewise for the weekday
+    /// - `%H`, `%M` and `%S` for hours, minutes and seconds, optionally with
+    ///   a fraction, `%f` for the fraction on its own, `%I` and `%p` for
+    ///   hours before or after noon
+    /// - `%z` for an offset from UTC like `Z`, `+01:00` or `-0530`
+    /// - `%s` for seconds since 1970
+    ///
+    /// A space stands for any amount of whitespace, anything else is matched
+    /// as it is, `%%` for a percent sign.
+    Pattern(String),
+}
+
+impl fmt::Display for DateFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DateFormat::Auto => "auto".fmt(f),
+            DateFormat::Pattern(pattern) => pattern.fmt(f),
+        }
+    }
+}
+
+impl FromStr for DateFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        if format.trim().is_empty() || format.eq_ignore_ascii_case("auto") {
+            return Ok(DateFormat::Auto);
+        }
+        date::validate(format)
+            .map(|()| DateFormat::Pattern(format.to_string()))
+            .map_err(|message| format!("Invalid date format \"{}\": {}", format, message))
+    }
+}
+
+/// What to search for and how the input is structured
+#[derive(Debug, Clone)]
+pub struct ScanConfig {
+    /// Every occurence of any of these terms is reported
+    pub search_terms: Vec<String>,
+    pub mode: SearchMode,
+    /// How many characters may differ from a term in a fuzzy search
+    pub max_distance: u32,
+    /// How words are compared in a phonetic search
+    pub phonetic: Phonetic,
+    pub case: CaseMode,
+    pub normalization: Normalization,
+    /// Whether letters match regardless of their diacritics, so "Muller"
+    /// matches "Müller". Letters like "ø" which don't decompose into a base
+    /// letter and diacritics keep them.
+    pub ignore_diacritics: bool,
+    pub scope: MatchScope,
+    /// Whether whitespace around a field is left out when a match has to
+    /// span the whole field
+    pub trim: bool,
+    /// Whether matches may overlap, like "aa" twice in "aaa". Otherwise a
+    /// match is only reported if it starts after the last one ended. The
+    /// matches of a single regular expression never overlap, only those of
+    /// different ones may.
+    pub overlapping: bool,
+    pub seperator: char,
+    /// The character enclosing fields which contain separators, line breaks
+    /// or quotes, `None` if fields are never quoted
+    pub quote: Option<char>,
+    /// Whether the first record holds the column names instead of data
+    pub has_header: bool,
+    /// How the characters are encoded, offsets are always counted in bytes
+    /// of the input as it is
+    pub encoding: Encoding,
+    /// Replace invalid characters and report them as [`InvalidSequence`]s
+    /// instead of ending the scan. Detected UTF-8 stays UTF-8 then, instead
+    /// of turning into Windows-1252 at the first invalid sequence.
+    pub lossy: bool,
+    /// Only fields in these columns are searched, all if it's empty
+    pub columns: Vec<Column>,
+    /// Only records whose values are in the range of every filter are
+    /// searched. If the search string is empty, every such record is
+    /// reported as a whole.
+    pub filters: Vec<Filter>,
+    /// How numbers in the input are written
+    pub decimal_separator: DecimalSeparator,
+    /// Globs selecting the files scanned below a directory, see
+    /// [`Scanner::stream`]
+    pub file_patterns: Vec<String>,
+    /// Globs selecting the members scanned in zip and tar archives, in the
+    /// same format as the file patterns. Unless one of them includes
+    /// members, only `*.csv`, `*.tsv` and `*.txt` files are scanned.
+    pub member_patterns: Vec<String>,
+}
+
+impl ScanConfig {
+    pub fn new(search_string: impl Into<String>) -> Self {
+        Self::with_terms([search_string])
+    }
+
+    /// Searches for all the given terms in a single pass.
+    pub fn with_terms(terms: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            search_terms: terms.into_iter().map(Into::into).collect(),
+            mode: SearchMode::default(),
+            max_distance: 1,
+            phonetic: Phonetic::default(),
+            case: CaseMode::default(),
+            normalization: Normalization::default(),
+            ignore_diacritics: false,
+            scope: MatchScope::default(),
+            trim: false,
+            overlapping: true,
+            seperator: ',',
+            quote: Some('"'),
+            has_header: true,
+            encoding: Encoding::default(),
+            lossy: false,
+            columns: Vec::new(),
+            filters: Vec::new(),
+            decimal_separator: DecimalSeparator::default(),
+            file_patterns: Vec::new(),
+            member_patterns: Vec::new(),
+        }
+    }
+
+    pub fn mode(mut self, mode: SearchMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn max_distance(mut self, max_distance: u32) -> Self {
+        self.max_distance = max_distance;
+        self
+    }
+
+    pub fn phonetic(mut self, phonetic: Phonetic) -> Self {
+        self.phonetic = phonetic;
+        self
+    }
+
+    pub fn case(mut self, case: CaseMode) -> Self {
+        self.case = case;
+        self
+    }
+
+    pub fn normalization(mut self, normalization: Normalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    pub fn ignore_diacritics(mut self, ignore_diacritics: bool) -> Self {
+        self.ignore_diacritics = ignore_diacritics;
+        self
+    }
+
+    pub fn scope(mut self, scope: MatchScope) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    pub fn trim(mut self, trim: bool) -> Self {
+        self.trim = trim;
+        self
+    }
+
+    pub fn overlapping(mut self, overlapping: bool) -> Self {
+        self.overlapping = overlapping;
+        self
+    }
+
+    pub fn seperator(mut self, seperator: char) -> Self {
+        self.seperator = seperator;
+        self
+    }
+
+    pub fn quote(mut self, quote: Option<char>) -> Self {
+        self.quote = quote;
+        self
+    }
+
+    pub fn has_header(mut self, has_header: bool) -> Self {
+        self.has_header = has_header;
+        self
+    }
+
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    pub fn lossy(mut self, lossy: bool) -> Self {
+        self.lossy = lossy;
+        self
+    }
+
+    pub fn columns(mut self, columns: impl IntoIterator<Item = Column>) -> Self {
+        self.columns = columns.into_iter().collect();
+        self
+    }
+
+    pub fn filters(mut self, filters: impl IntoIterator<Item = Filter>) -> Self {
+        self.filters = filters.into_iter().collect();
+        self
+    }
+
+    pub fn decimal_separator(mut self, decimal_separator: DecimalSeparator) -> Self {
+        self.decimal_separator = decimal_separator;
+        self
+    }
+
+    /// Only scans files below a directory which match one of these globs,
+    /// e.g. `*.csv`, and none of those starting with `!`, e.g. `!archive/**`.
+    pub fn file_patterns(mut self, patterns: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.file_patterns = patterns.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Only scans members of archives which match one of these globs, like
+    /// [`ScanConfig::file_patterns`].
+    pub fn member_patterns(
+        mut self,
+        patterns: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.member_patterns = patterns.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Checks the search terms without starting a scan, e.g. whether they
+    /// are valid regular expressions.
+    pub fn validate(&self) -> Result<(), ScanError> {
+        self.validate_columns()?;
+        FileFilter::new(&self.file_patterns)?;
+        FileFilter::new(&self.member_patterns)?;
+        Query::parse_all(self)?;
+        Matcher::new(self).map(|_| ())
+    }
+
+    fn validate_columns(&self) -> Result<(), ScanError> {
+        let mut columns = self
+            .columns
+            .iter()
+            .chain(self.filters.iter().map(|filter| &filter.column));
+        if columns.clone().any(|column| *column == Column::Number(0)) {
+            return Err(ScanError::ColumnZero);
+        }
+        if !self.has_header
+            && let Some(Column::Name(name)) =
+                columns.find(|column| matches!(column, Column::Name(_)))
+        {
+            return Err(ScanError::UnknownColumn(name.clone()));
+        }
+        Ok(())
+    }
+
+    /// Whether there's nothing to search for but records passing the
+    /// filters, which are then reported as a whole
+    fn only_filters(&self) -> bool {
+        !self.filters.is_empty()
+            && self.mode != SearchMode::Query
+            && matches!(self.search_terms.as_slice(), [term] if term.is_empty())
+    }
+}
+
+/// A single match of a search term, positioned at its last character
+///
+/// Offsets of compressed files count the bytes of the decompressed content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Occurence {
+    /// The file the match is in, unless a reader was scanned
+    pub path: Option<Arc<Path>>,
+    /// Index of the matched term in [`ScanConfig::search_terms`]
+    pub term: usize,
+    /// Which CSV record the match is in, counting the header as the first
+    pub record_number: u64,
+    /// Which field of the record the match is in, starting at 1, or 0 for a
+    /// whole record matching a query or passing the filters
+    pub column_number: u64,
+    /// The header of that column, if there is one
+    pub column_name: Option<Arc<str>>,
+    /// Which line the last character is on, counting line breaks inside of
+    /// quoted fields as well
+    pub line_number: u64,
+    pub line_character_offset: u64,
+    pub line_byte_offset: u64,
+    pub total_byte_offset: u64,
+    /// How many characters the matched value has
+    pub match_length: u64,
+    /// How many bytes the match spans in the input, including escaped quotes
+    pub match_byte_length: u64,
+    /// The matched value as it is in the field, which may differ from the
+    /// term in more than case
+    pub matched: Arc<str>,
+    /// How many characters had to be inserted, left out or replaced to match
+    /// the term, 0 unless the search is fuzzy
+    pub distance: u32,
+}
+
+/// Bytes which aren't a valid character in the encoding, replaced by
+/// U+FFFD in lossy mode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidSequence {
+    pub path: Option<Arc<Path>>,
+    pub line_number: u64,
+    /// Where the sequence starts in the line
+    pub line_byte_offset: u64,
+    /// Where the sequence starts in the input
+    pub total_byte_offset: u64,
+    /// How many bytes were replaced
+    pub length: u64,
+}
+
+/// A value of a filtered column which isn't a number or date the filter can
+/// compare, so the record isn't searched
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnparseableValue {
+    pub path: Option<Arc<Path>>,
+    /// Which line the value starts on
+    pub line_number: u64,
+    pub record_number: u64,
+    /// Starting at 1
+    pub column_number: u64,
+    pub column_name: Option<Arc<str>>,
+    pub value: Arc<str>,
+}
+
+/// A batch of occurences found since the previous update
+#[derive(Debug, Clone)]
+pub struct ScanUpdate {
+    /// The file currently being scanned
+    pub path: Arc<Path>,
+    /// How many bytes of that file have been scanned
+    pub file_scanned: u64,
+    /// How many files have been scanned completely
+    pub files_scanned: u64,
+    /// How many bytes of all files have been scanned, after decompressing
+    /// them
+    pub now_scanned: u64,
+    /// How many bytes of all files have been read, which differs from
+    /// `now_scanned` for compressed files
+    pub now_read: u64,
+    pub occurences: Vec<Occurence>,
+    /// Invalid characters replaced since the previous update, only in lossy
+    /// mode
+    pub invalid_sequences: Vec<InvalidSequence>,
+    /// Values of filtered columns which couldn't be compared since the
+    /// previous update
+    pub unparseable_values: Vec<UnparseableValue>,
+}
+
+#[derive(Debug)]
+pub enum ScanError {
+    Io(io::Error),
+    InvalidUtf8 {
+        total_byte_offset: u64,
+    },
+    InvalidUtf16 {
+        total_byte_offset: u64,
+    },
+    InvalidPattern {
+        term: String,
+        message: String,
+    },
+    InvalidQuery {
+        query: String,
+        message: String,
+    },
+    InvalidFilter {
+        filter: String,
+        message: String,
+    },
+    UnknownColumn(String),
+    /// A column was given by the number 0
+    ColumnZero,
+    /// A term of a phonetic search has no letters to compare the sound of
+    Unpronounceable(String),
+    /// Something went wrong with a single file of a directory
+    InFile {
+        path: PathBuf,
+        error: Box<ScanError>,
+    },
+}
+
+impl ScanError {
+    pub fn in_file(path: impl Into<PathBuf>, error: impl Into<ScanError>) -> Self {
+        ScanError::InFile {
+            path: path.into(),
+            error: Box::new(error.into()),
+        }
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanError::Io(err) => err.fmt(f),
+            ScanError::InvalidUtf8 { total_byte_offset } => {
+                write!(f, "Invalid UTF-8 sequence at byte {}", total_byte_offset)
+            }
+            ScanError::InvalidUtf16 { total_byte_offset } => {
+                write!(f, "Invalid UTF-16 sequence at byte {}", total_byte_offset)
+            }
+            ScanError::InvalidPattern { term, message } => {
+                write!(f, "Invalid pattern \"{}\": {}", term, message)
+            }
+            ScanError::InvalidQuery { query, message } => {
+                write!(f, "Invalid query \"{}\": {}", query, message)
+            }
+            ScanError::InvalidFilter { filter, message } => {
+                write!(f, "Invalid filter \"{}\": {}", filter, message)
+            }
+            ScanError::UnknownColumn(name) => {
+                write!(f, "There is no column named \"{}\"", name)
+            }
+            ScanError::ColumnZero => "Columns are numbered from 1".fmt(f),
+            ScanError::Unpronounceable(term) => {
+                write!(f, "\"{}\" has no letters to compare the sound of", term)
+            }
+            ScanError::InFile { path, error } => write!(f, "{}: {}", path.display(), error),
+        }
+    }
+}
+
+impl std::error::Error for ScanError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ScanError::Io(err) => Some(err),
+            ScanError::InFile { error, .. } => Some(error),
+            ScanError::InvalidUtf8 { .. }
+            | ScanError::InvalidUtf16 { .. }
+            | ScanError::InvalidPattern { .. }
+            | ScanError::InvalidQuery { .. }
+            | ScanError::InvalidFilter { .. }
+            | ScanError::UnknownColumn(_)
+            | ScanError::ColumnZero
+            | ScanError::Unpronounceable(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for ScanError {
+    fn from(err: io::Error) -> Self {
+        ScanError::Io(err)
+    }
+}
+
+/// Searches CSV input for a string, either blocking through [`Scanner::scan`]
+/// or asynchronously through [`Scanner::stream`].
+#[derive(Debug, Clone)]
+pub struct Scanner {
+    config: ScanConfig,
+    matcher: Matcher,
+    // Empty unless records are matched by queries
+    queries: Vec<Query>,
+    filter: FileFilter,
+    member_filter: FileFilter,
+}
+
+impl Scanner {
+    /// Fails if the search terms or file patterns are invalid or columns are
+    /// selected by name without a header.
+    pub fn new(config: ScanConfig) -> Result<Self, ScanError> {
+        config.validate_columns()?;
+        let filter = FileFilter::new(&config.file_patterns)?;
+        let member_filter = FileFilter::members(&config.member_patterns)?;
+        let queries = Query::parse_all(&config)?;
+        let matcher = Matcher::new(&config)?;
+        Ok(Self {
+            config,
+            matcher,
+            queries,
+            filter,
+            member_filter,
+        })
+    }
+
+    pub fn config(&self) -> &ScanConfig {
+        &self.config
+    }
+
+    /// Scans the given reader on the current thread.
+    pub fn scan<R: Read>(&self, reader: R) -> Scan<BufReader<R>> {
+        self.scan_named(reader, None)
+    }
+
+    /// Scans a file on the current thread, decompressing it on the fly if
+    /// it's compressed.
+    pub fn scan_file(&self, path: impl AsRef<Path>) -> io::Result<Scan<Input<'static>>> {
+        let path = path.as_ref();
+        let input = Input::new(File::open(path)?)?;
+        Ok(self.scan_buffered(input, Some(Arc::from(path))))
+    }
+
+    /// Scans a file on the current thread like [`Scanner::scan_file`], or
+    /// every member matching [`ScanConfig::member_patterns`] if it's a zip or
+    /// tar archive.
+    ///
+    /// `on_scan` is called with the scan of each of them, or the error
+    /// opening a member, until it breaks. Members are named
+    /// `archive!member`, so every occurence shows where it came from.
+    pub fn scan_path(
+        &self,
+        path: &Path,
+        mut on_scan: impl FnMut(Result<Scan<Input<'_>>, ScanError>) -> ControlFlow<()>,
+    ) -> Result<(), ScanError> {
+        let in_file = |err| ScanError::in_file(path, err);
+
+        let Some(mut archive) = Archive::open(path).map_err(in_file)? else {
+            let scan = self.scan_file(path).map_err(in_file)?;
+            let _ = on_scan(Ok(scan));
+            return Ok(());
+        };
+
+        archive
+            .for_each_member(&self.member_filter, |name, member| {
+                let path = PathBuf::from(format!("{}!{}", path.display(), name));
+                on_scan(match member {
+                    Ok(member) => Ok(self.scan_buffered(member, Some(Arc::from(path)))),
+                    Err(err) => Err(ScanError::in_file(path, err)),
+                })
+            })
+            .map_err(in_file)
+    }
+
+    fn scan_named<R: Read>(&self, reader: R, path: Option<Arc<Path>>) -> Scan<BufReader<R>> {
+        self.scan_buffered(BufReader::with_capacity(BUFFER_SIZE, reader), path)
+    }
+
+    /// Scans a reader with a buffer of its own, like a mapped file.
+    fn scan_buffered<R: BufRead>(&self, reader: R, path: Option<Arc<Path>>) -> Scan<R> {
+        Scan::new(reader, ScanState::new(self, path))
+    }
+
+    /// The files to scan below `root` in the order they are scanned, or just
+    /// `root` if it's a file.
+    pub fn files<'a>(
+        &'a self,
+        root: &'a Path,
+    ) -> impl Iterator<Item = Result<PathBuf, ScanError>> + 'a {
+        self.filter.files(root)
+    }
+
+    /// Scans the file at `path`, or every file below it if it's a directory,
+    /// on a dedicated thread.
+    ///
+    /// Large plain files are split into chunks which are scanned on every
+    /// core, their occurences are still sent in file order.
+    ///
+    /// Errors of single files are sent as [`ScanError::InFile`] and the scan
+    /// goes on with the next file. It stops as soon as the returned stream
+    /// is dropped.
+    pub fn stream(&self, path: impl Into<PathBuf>) -> ScanStream {
+        let root = path.into();
+        let scanner = self.clone();
+        let (sender, receiver) = mpsc::channel(16);
+
+        thread::spawn(move || {
+            let mut progress = Progress::default();
+
+            for path in scanner.files(&root) {
+                let result = match path {
+                    Ok(path) => scanner.stream_file(&path, &mut progress, &sender),
+                    Err(err) => sender.blocking_send(Err(err)).map_err(|_| ()),
+                };
+                if result.is_err() {
+                    // nobody is listening anymore
+                    return;
+                }
+            }
+        });
+
+        ScanStream { receiver }
+    }
+
+    /// Sends the updates for a single file, or each member of an archive,
+    /// fails if the receiver is gone.
+    fn stream_file(
+        &self,
+        path: &Path,
+        progress: &mut Progress,
+        sender: &mpsc::Sender<Result<ScanUpdate, ScanError>>,
+    ) -> Result<(), ()> {
+        if let Some(result) = self.stream_parallel(path, progress, sender) {
+            return result;
+        }
+
+        let mut listening = Ok(());
+        let result = self.scan_path(path, |scan| {
+            listening = match scan {
+                Ok(scan) => Self::stream_scan(scan, progress, sender),
+                Err(err) => sender.blocking_send(Err(err)).map_err(|_| ()),
+            };
+            match listening {
+                Ok(()) => ControlFlow::Continue(()),
+                Err(()) => ControlFlow::Break(()),
+            }
+        });
+
+        listening?;
+        match result {
+            Ok(()) => Ok(()),
+            Err(err) => sender.blocking_send(Err(err)).map_err(|_| ()),
+        }
+    }
+
+    fn stream_scan(
+        mut scan: Scan<Input<'_>>,
+        progress: &mut Progress,
+        sender: &mpsc::Sender<Result<ScanUpdate, ScanError>>,
+    ) -> Result<(), ()> {
+        let send = |result| sender.blocking_send(result).map_err(|_| ());
+        // scans of files always have a path
+        let path = scan
+            .state
+            .path
+            .clone()
+            .unwrap_or_else(|| Arc::from(Path::new("")));
+
+        let mut occurences = Vec::new();
+        // the progress doesn't include this file yet
+        let update = |scan: &mut Scan<_>, progress: &Progress, done: bool, occurences| {
+            Ok(ScanUpdate {
+                path: path.clone(),
+                file_scanned: scan.bytes_scanned(),
+                files_scanned: progress.files + u64::from(done),
+                now_scanned: progress.bytes + scan.bytes_scanned(),
+                now_read: progress.read + scan.file_read(),
+                occurences,
+                invalid_sequences: scan.take_invalid_sequences(),
+                unparseable_values: scan.take_unparseable_values(),
+            })
+        };
+
+        loop {
+            let pause_at = scan.bytes_scanned() + UPDATE_INTERVAL;
+            match scan.step(pause_at) {
+                Ok(Step::Found(occurence)) => occurences.push(occurence),
+                Ok(Step::Paused) => send(update(
+                    &mut scan,
+                    progress,
+                    false,
+                    mem::take(&mut occurences),
+                ))?,
+                Ok(Step::Finished) => break,
+                Err(err) => {
+                    send(update(
+                        &mut scan,
+                        progress,
+                        true,
+                        mem::take(&mut occurences),
+                    ))?;
+                    progress.finish(&scan);
+                    return send(Err(ScanError::in_file(path.as_ref(), err)));
+                }
+            }
+        }
+
+        send(update(&mut scan, progress, true, occurences))?;
+        progress.finish(&scan);
+        Ok(())
+    }
+}
+
+/// What has been scanned of the previous files
+#[derive(Debug, Default)]
+struct Progress {
+    files: u64,
+    bytes: u64,
+    read: u64,
+}
+
+impl Progress {
+    fn finish(&mut self, scan: &Scan<Input<'_>>) {
+        self.add(scan.bytes_scanned(), scan.file_read());
+    }
+
+    fn add(&mut self, scanned: u64, read: u64) {
+        self.files += 1;
+        self.bytes += scanned;
+        self.read += read;
+    }
+}
+
+/// Progress of a scan running in the background, see [`Scanner::stream`]
+pub struct ScanStream {
+    receiver: mpsc::Receiver<Result<ScanUpdate, ScanError>>,
+}
+
+impl ScanStream {
+    /// Waits for the next update on the current thread, for callers without
+    /// an async runtime.
+    pub fn blocking_next(&mut self) -> Option<Result<ScanUpdate, ScanError>> {
+        self.receiver.blocking_recv()
+    }
+}
+
+impl Stream for ScanStream {
+    type Item = Result<ScanUpdate, ScanError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+/// Blocking iterator over the occurences in a reader, see [`Scanner::scan`]
+///
+/// UTF-8 input is taken a buffer at a time, at about 60 MB/s per core on the
+/// input of `benches/scan.rs`, where reading a byte at a time as the app used
+/// to managed about 40 MB/s. Skipped records go by at about 500 MB/s.
+pub struct Scan<R> {
+    reader: R,
+    state: ScanState,
+    // Occurences found but not yet returned
+    pending: VecDeque<Occurence>,
+    // Whether the end of the input or an error was reached
+    finished: bool,
+}
+
+impl<R: BufRead> Scan<R> {
+    fn new(reader: R, state: ScanState) -> Self {
+        Self {
+            reader,
+            state,
+            pending: VecDeque::new(),
+            finished: false,
+        }
+    }
+
+    /// How many bytes have been consumed from the reader so far
+    pub fn bytes_scanned(&self) -> u64 {
+        self.state.position.total_byte_offset
+    }
+
+    /// The file being scanned, unless a reader was given
+    pub fn path(&self) -> Option<&Path> {
+        self.state.path.as_deref()
+    }
+
+    /// The invalid characters replaced since this was called last, only in
+    /// lossy mode
+    pub fn take_invalid_sequences(&mut self) -> Vec<InvalidSequence> {
+        mem::take(&mut self.state.invalid_sequences)
+    }
+
+    /// The values of filtered columns which couldn't be compared since this
+    /// was called last
+    pub fn take_unparseable_values(&mut self) -> Vec<UnparseableValue> {
+        mem::take(&mut self.state.unparseable_values)
+    }
+
+    /// The column names, once the header has been read
+    pub fn header(&self) -> &[Arc<str>] {
+        &self.state.header
+    }
+
+    /// Reads just the header, e.g. to choose the columns to search.
+    pub fn read_header(&mut self) -> Result<&[Arc<str>], ScanError> {
+        while !self.finished && self.state.record_number == 1 {
+            // a character at a time, so nothing after the header is scanned
+            if let Err(err) = self.advance(self.bytes_scanned() + 1) {
+                self.finished = true;
+                return Err(err);
+            }
+        }
+
+        Ok(self.header())
+    }
+
+    /// Reads until the next occurence, the end of the input or until
+    /// `pause_at` bytes have been scanned, whichever comes first.
+    fn step(&mut self, pause_at: u64) -> Result<Step, ScanError> {
+        loop {
+            if let Some(occurence) = self.pending.pop_front() {
+                return Ok(Step::Found(occurence));
+            }
+            if self.finished {
+                return Ok(Step::Finished);
+            }
+            if self.bytes_scanned() >= pause_at {
+                return Ok(Step::Paused);
+            }
+
+            if let Err(err) = self.advance(pause_at) {
+                self.finished = true;
+                return Err(err);
+            }
+        }
+    }
+
+    /// Scans at least one character, and as much of the reader's buffer as
+    /// possible without going past `pause_at`.
+    ///
+    /// Valid UTF-8 is taken a buffer at a time, everything else, like other
+    /// encodings, invalid bytes or a character cut off by the end of the
+    /// buffer, is decoded one character at a time.
+    fn advance(&mut self, pause_at: u64) -> Result<(), ScanError> {
+        if self.state.encoding == Encoding::Auto {
+            let start = self.reader.fill_buf()?;
+            self.state.encoding = Encoding::detect(start);
+            // invalid sequences are replaced in lossy mode instead
+            self.state.fallback = !self.state.lossy && !self.state.encoding.is_certain(start);
+        }
+
+        if self.state.encoding == Encoding::Utf8 {
+            let limit = pause_at.saturating_sub(self.bytes_scanned());
+            // a mapped file is a single buffer, which is validated a part at
+            // a time
+            let buffer = self.reader.fill_buf()?;
+            let buffer = &buffer[..buffer.len().min(BUFFER_SIZE)];
+            // up to an invalid byte or a character cut off by the end of the
+            // buffer, if there is one
+            let text = std::str::from_utf8(buffer)
+                .or_else(|err| std::str::from_utf8(&buffer[..err.valid_up_to()]))
+                .unwrap_or_default();
+
+            let mut len = text.len().min(usize::try_from(limit).unwrap_or(usize::MAX));
+            while !text.is_char_boundary(len) {
+                len -= 1;
+            }
+            if len > 0 {
+                self.state.push_str(&text[..len], &mut self.pending)?;
+                self.reader.consume(len);
+                return Ok(());
+            }
+        }
+
+        if self.state.fallback {
+            return self.read_utf8_or_windows_1252();
+        }
+
+        match self.read_char()? {
+            Some((char, len)) => self.state.push(char, len, &mut self.pending),
+            None => {
+                self.finished = true;
+                self.state.finish(&mut self.pending)
+            }
+        }
+    }
+
+    /// Reads a character of detected UTF-8, or switches to Windows-1252 if
+    /// it's an invalid sequence and reads its bytes in that instead.
+    fn read_utf8_or_windows_1252(&mut self) -> Result<(), ScanError> {
+        match encoding::read_utf8(&mut self.reader)? {
+            Some(Ok((char, len))) => self.state.push(char, len, &mut self.pending),
+            Some(Err(bytes)) => {
+                self.state.switch_encoding(Encoding::Windows1252);
+                self.state.fallback = false;
+                bytes.into_iter().try_for_each(|byte| {
+                    self.state
+                        .push(encoding::windows_1252(byte), 1, &mut self.pending)
+                })
+            }
+            None => {
+                self.finished = true;
+                self.state.finish(&mut self.pending)
+            }
+        }
+    }
+
+    fn read_char(&mut self) -> Result<Option<(char, usize)>, ScanError> {
+        match self.state.encoding.read_char(&mut self.reader)? {
+            None => Ok(None),
+            Some(Decoded::Char(char, len)) => Ok(Some((char, len))),
+            Some(Decoded::Invalid(len)) if self.state.lossy => {
+                let position = self.state.position;
+                self.state.invalid_sequences.push(InvalidSequence {
+                    path: self.state.path.clone(),
+                    line_number: position.line_number,
+                    line_byte_offset: position.line_byte_offset,
+                    total_byte_offset: position.total_byte_offset,
+                    length: len as u64,
+                });
+                Ok(Some((char::REPLACEMENT_CHARACTER, len)))
+            }
+            Some(Decoded::Invalid(_)) => {
+                let total_byte_offset = self.bytes_scanned();
+                Err(match self.state.encoding {
+                    Encoding::Utf16Le | Encoding::Utf16Be => {
+                        ScanError::InvalidUtf16 { total_byte_offset }
+                    }
+                    _ => ScanError::InvalidUtf8 { total_byte_offset },
+                })
+            }
+        }
+    }
+}
+
+impl Scan<Input<'_>> {
+    /// How the file is compressed, if it is
+    pub fn compression(&self) -> Option<Compression> {
+        self.reader.compression()
+    }
+
+    /// How many bytes have been read from the file, which differs from
+    /// [`Scan::bytes_scanned`] if it's compressed or an archive
+    pub fn file_read(&self) -> u64 {
+        self.reader
+            .file_read()
+            .unwrap_or_else(|| self.bytes_scanned())
+    }
+}
+
+impl<R: BufRead> Iterator for Scan<R> {
+    type Item = Result<Occurence, ScanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.step(u64::MAX) {
+            Ok(Step::Found(occurence)) => Some(Ok(occurence)),
+            Ok(Step::Paused | Step::Finished) => None,
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+enum Step {
+    Found(Occurence),
+    Paused,
+    Finished,
+}
+
+/// Where in the input a character is
+#[derive(Debug, Clone, Copy)]
+struct Position {
+    // Which line we're currently on
+    line_number: u64,
+    // Which character we're currently on in the line
+    line_character_offset: u64,
+    // Which byte that character is at
+    line_byte_offset: u64,
+    // Which byte that character is at in total
+    total_byte_offset: u64,
+}
+
+impl Position {
+    fn advance(&mut self, char: char, len: usize) {
+        if char == '\n' {
+            self.line_number += 1;
+            self.line_character_offset = 0;
+            self.line_byte_offset = 0;
+        } else {
+            // how many characters in we are
+            self.line_character_offset += 1;
+            // how many bytes that character is at
+            self.line_byte_offset += len as u64;
+        }
+        // Which byte that character is at in total
+        self.total_byte_offset += len as u64;
+    }
+
+    /// Advances over UTF-8 text, like [`Position::advance`] for every
+    /// character but without decoding them one by one.
+    fn advance_str(&mut self, text: &str) {
+        let line = match text.rfind('\n') {
+            Some(newline) => {
+                self.line_number += text.bytes().filter(|&byte| byte == b'\n').count() as u64;
+                self.line_character_offset = 0;
+                self.line_byte_offset = 0;
+                &text[newline + 1..]
+            }
+            None => text,
+        };
+        self.line_character_offset += line.chars().count() as u64;
+        self.line_byte_offset += line.len() as u64;
+        self.total_byte_offset += text.len() as u64;
+    }
+}
+
+/// Splits the input into fields and hands them to the [`Matcher`]
+struct ScanState {
+    path: Option<Arc<Path>>,
+    // Detected when the first character is read, unless it's configured
+    encoding: Encoding,
+    // Whether detected UTF-8 turns into Windows-1252 at the first invalid
+    // sequence
+    fallback: bool,
+    lossy: bool,
+    // Replaced since they were taken last
+    invalid_sequences: Vec<InvalidSequence>,
+    matcher: Matcher,
+    queries: Vec<Query>,
+    // Where the columns of every query are, once the header has been read
+    query_columns: Vec<Vec<usize>>,
+    filters: Vec<Filter>,
+    // Index of the column of every filter, once the header has been read
+    filter_columns: Vec<usize>,
+    // Whether the current record is in the range of every filter so far
+    filter_met: Vec<bool>,
+    decimal_separator: DecimalSeparator,
+    // Not comparable since they were taken last
+    unparseable_values: Vec<UnparseableValue>,
+    // Whether records are reported as a whole instead of matches in fields
+    whole_records: bool,
+    // Skips records without a possible match, if the search allows it
+    prefilter: Option<Prefilter>,
+    tokenizer: Tokenizer,
+    position: Position,
+    // Which record we're currently in
+    record_number: u64,
+    // Which field of the record we're currently in, starting at 0
+    column: usize,
+    has_header: bool,
+    header: Vec<Arc<str>>,
+    columns: Vec<Column>,
+    // Whether a column is searched by index, `None` if all of them are
+    selected: Option<Vec<bool>>,
+    // The value of the field read so far
+    field: String,
+    // Where the characters of the field are in the input. Each entry is the
+    // index in the field and the position right before the character at it,
+    // recorded at the start of the field and whenever syntax was skipped.
+    field_positions: Vec<(usize, Position)>,
+    // Whether syntax was skipped since the last character of the field
+    skipped: bool,
+    // Where the last character was and how long it was, if it's a carriage
+    // return which may still end up in the field
+    carriage_return: Option<(Position, usize)>,
+    // Index in the field and length in the input of every replaced invalid
+    // character, whose length differs from a valid one
+    replaced: Vec<(usize, usize)>,
+    // The fields of the current record so far, only kept for whole records
+    record: Vec<String>,
+    // Matches in the current record, held back until it has passed the
+    // filters
+    record_occurences: Vec<Occurence>,
+    // Where the current record starts, and where its last field read so far
+    // ends
+    record_start: Position,
+    record_end: Position,
+    seperator: char,
+}
+
+/// Which of `len` columns or more are selected by their number.
+fn select_numbers(columns: &[Column], len: usize) -> Vec<bool> {
+    let mut selected = vec![false; len];
+    for column in columns {
+        if let Column::Number(number) = column {
+            let index = (*number as usize).saturating_sub(1);
+            if index >= selected.len() {
+                selected.resize(index + 1, false);
+            }
+            selected[index] = true;
+        }
+    }
+    selected
+}
+
+impl ScanState {
+    fn new(scanner: &Scanner, path: Option<Arc<Path>>) -> Self {
+        let config = &scanner.config;
+        let whole_records = !scanner.queries.is_empty() || config.only_filters();
+        // queries name their columns themselves
+        let columns = if !whole_records {
+            config.columns.clone()
+        } else {
+            Vec::new()
+        };
+        let start = Position {
+            line_number: 1,
+            line_character_offset: 0,
+            line_byte_offset: 0,
+            total_byte_offset: 0,
+        };
+
+        Self {
+            path,
+            encoding: config.encoding,
+            fallback: false,
+            lossy: config.lossy,
+            invalid_sequences: Vec::new(),
+            matcher: scanner.matcher.clone(),
+            queries: scanner.queries.clone(),
+            query_columns: Vec::new(),
+            filters: config.filters.clone(),
+            // named columns are resolved with the header
+            filter_columns: config
+                .filters
+                .iter()
+                .map(|filter| match filter.column {
+                    Column::Number(number) => (number as usize).saturating_sub(1),
+                    Column::Name(_) => usize::MAX,
+                })
+                .collect(),
+            filter_met: vec![false; config.filters.len()],
+            decimal_separator: config.decimal_separator,
+            unparseable_values: Vec::new(),
+            whole_records,
+            prefilter: Prefilter::new(config),
+            tokenizer: Tokenizer::new(config.seperator, config.quote),
+            position: start,
+            record_number: 1,
+            column: 0,
+            has_header: config.has_header,
+            header: Vec::new(),
+            // without a header there can only be numbers, which are
+            // selected before the first record
+            selected: (!config.has_header && !columns.is_empty())
+                .then(|| select_numbers(&columns, 0)),
+            columns,
+            field: String::new(),
+            field_positions: vec![(0, start)],
+            skipped: false,
+            carriage_return: None,
+            replaced: Vec::new(),
+            record: Vec::new(),
+            record_occurences: Vec::new(),
+            record_start: start,
+            record_end: start,
+            seperator: config.seperator,
+        }
+    }
+
+    fn push(
+        &mut self,
+        char: char,
+        len: usize,
+        occurences: &mut VecDeque<Occurence>,
+    ) -> Result<(), ScanError> {
+        let before = self.position;
+        self.position.advance(char, len);
+        // a carriage return not followed by a line feed is part of the field
+        if let Some((position, len)) = self.carriage_return.take()
+            && char != '\n'
+        {
+            self.push_char('\r', len, position);
+        }
+
+        match self.tokenizer.push(char) {
+            Token::Char(char) => self.push_char(char, len, before),
+            Token::Skip => self.skipped = true,
+            Token::CarriageReturn => self.carriage_return = Some((before, len)),
+            Token::FieldEnd => self.end_field(occurences),
+            Token::RecordEnd => {
+                self.end_field(occurences);
+                self.end_record(occurences)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds a character at `before` in the input to the field.
+    fn push_char(&mut self, char: char, len: usize, before: Position) {
+        // fields of other columns are skipped entirely
+        if self.is_kept(self.column) {
+            if self.skipped {
+                self.field_positions.push((self.field.len(), before));
+                self.skipped = false;
+            }
+            if len != self.encoding.char_len(char) {
+                self.replaced.push((self.field.len(), len));
+            }
+            self.field.push(char);
+        }
+    }
+
+    /// Decodes the rest of the input in another encoding. The characters of
+    /// the field so far keep the length they had in the previous one.
+    fn switch_encoding(&mut self, encoding: Encoding) {
+        // a pending carriage return goes into the field before what follows
+        let position = match self.carriage_return {
+            Some((position, _)) => position,
+            None => self.position,
+        };
+        self.field_positions.push((self.field.len(), position));
+        self.encoding = encoding;
+    }
+
+    /// Handles valid UTF-8 input, taking runs of ordinary field characters
+    /// at once and only the characters in between one at a time.
+    fn push_str(
+        &mut self,
+        mut text: &str,
+        occurences: &mut VecDeque<Occurence>,
+    ) -> Result<(), ScanError> {
+        while let Some(char) = text.chars().next() {
+            let (skippable, records) = self.skippable(text);
+            if skippable > 0 {
+                self.skip_records(&text[..skippable], records);
+                text = &text[skippable..];
+                continue;
+            }
+
+            let run = self.tokenizer.run_len(text);
+            if run > 0 {
+                self.push_run(&text[..run]);
+                text = &text[run..];
+            } else {
+                self.push(char, char.len_utf8(), occurences)?;
+                text = &text[char.len_utf8()..];
+            }
+        }
+
+        Ok(())
+    }
+
+    /// How many bytes of whole records at the start of `text` the
+    /// prefilter rules out once the header has been read, and how many
+    /// records they are.
+    fn skippable(&self, text: &str) -> (usize, u64) {
+        match &self.prefilter {
+            Some(prefilter) if self.record_number > 1 && self.at_record_start() => {
+                prefilter.skippable(text)
+            }
+            _ => (0, 0),
+        }
+    }
+
+    /// Whether the next character starts a record
+    fn at_record_start(&self) -> bool {
+        self.column == 0 && self.tokenizer.at_field_start()
+    }
+
+    /// Moves past whole records without splitting them into fields.
+    fn skip_records(&mut self, records: &str, count: u64) {
+        self.position.advance_str(records);
+        self.record_number += count;
+        self.field_positions.clear();
+        self.field_positions.push((0, self.position));
+        self.skipped = false;
+    }
+
+    /// Adds characters the tokenizer takes as they are to the field.
+    fn push_run(&mut self, run: &str) {
+        if self.is_kept(self.column) {
+            if self.skipped {
+                self.field_positions.push((self.field.len(), self.position));
+                self.skipped = false;
+            }
+            self.field.push_str(run);
+        }
+        self.position.advance_str(run);
+    }
+
+    /// Handles the last field at the end of the input.
+    fn finish(&mut self, occurences: &mut VecDeque<Occurence>) -> Result<(), ScanError> {
+        if let Some((position, len)) = self.carriage_return.take() {
+            self.push_char('\r', len, position);
+        }
+        self.end_field(occurences);
+        self.end_record(occurences)
+    }
+
+    fn in_header(&self) -> bool {
+        self.has_header && self.record_number == 1
+    }
+
+    /// Whether the characters of a field in the column are needed
+    fn is_kept(&self, column: usize) -> bool {
+        self.in_header() || self.is_selected(column) || self.filter_columns.contains(&column)
+    }
+
+    fn is_selected(&self, column: usize) -> bool {
+        match &self.selected {
+            Some(selected) => selected.get(column).copied().unwrap_or(false),
+            None => true,
+        }
+    }
+
+    fn end_record(&mut self, occurences: &mut VecDeque<Occurence>) -> Result<(), ScanError> {
+        let passed = self.filter_met.iter().all(|met| *met);
+        self.filter_met.fill(false);
+        if self.in_header() {
+            self.resolve_columns()?;
+            if !self.columns.is_empty() {
+                self.select_columns()?;
+            }
+        } else if self.whole_records {
+            self.match_record(passed, occurences);
+        } else if passed {
+            occurences.extend(self.record_occurences.drain(..));
+        } else {
+            self.record_occurences.clear();
+        }
+        self.record_number += 1;
+        self.column = 0;
+        Ok(())
+    }
+
+    /// Resolves the columns of the queries and filters by their names in the
+    /// header.
+    fn resolve_columns(&mut self) -> Result<(), ScanError> {
+        self.query_columns = self
+            .queries
+            .iter()
+            .map(|query| query.resolve(&self.header))
+            .collect::<Result<_, _>>()?;
+
+        for (filter, index) in self.filters.iter().zip(&mut self.filter_columns) {
+            if let Column::Name(name) = &filter.column {
+                *index = self
+                    .header
+                    .iter()
+                    .position(|column| column.trim() == name.trim())
+                    .ok_or_else(|| ScanError::UnknownColumn(name.clone()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolves the selected columns once the header is known.
+    fn select_columns(&mut self) -> Result<(), ScanError> {
+        let mut selected = select_numbers(&self.columns, self.header.len());
+
+        for column in &self.columns {
+            match column {
+                Column::Number(_) => {}
+                Column::Name(name) => {
+                    let mut found = false;
+                    for (index, column_name) in self.header.iter().enumerate() {
+                        if column_name.trim() == name.trim() {
+                            selected[index] = true;
+                            found = true;
+                        }
+                    }
+                    if !found {
+                        return Err(ScanError::UnknownColumn(name.clone()));
+                    }
+                }
+            }
+        }
+
+        self.selected = Some(selected);
+        Ok(())
+    }
+
+    fn end_field(&mut self, occurences: &mut VecDeque<Occurence>) {
+        if self.in_header() {
+            // a byte order mark isn't part of the first column name
+            self.header
+                .push(Arc::from(self.field.trim_start_matches('\u{feff}')));
+        } else {
+            if !self.filters.is_empty() {
+                self.apply_filters();
+            }
+
+            if self.whole_records {
+                if self.column == 0 {
+                    self.record_start = self.field_positions[0].1;
+                }
+                self.record_end = self.position_before(self.field.len());
+                self.record.push(mem::take(&mut self.field));
+            } else if !self.field.is_empty() && self.is_selected(self.column) {
+                let mut matches = Vec::new();
+                self.matcher.find(&self.field, |found| matches.push(found));
+                let found = matches.into_iter().map(|found| self.occurence(found));
+                if self.filters.is_empty() {
+                    occurences.extend(found);
+                } else {
+                    // held back until the record has passed the filters
+                    let found = found.collect::<Vec<_>>();
+                    self.record_occurences.extend(found);
+                }
+            }
+        }
+
+        self.column += 1;
+        self.field.clear();
+        self.field_positions.clear();
+        self.field_positions.push((0, self.position));
+        self.skipped = false;
+        self.replaced.clear();
+    }
+
+    /// How many bytes the character at `index` of the field takes in the
+    /// input
+    fn char_len(&self, index: usize, char: char) -> usize {
+        match self
+            .replaced
+            .binary_search_by_key(&index, |(index, _)| *index)
+        {
+            Ok(replaced) => self.replaced[replaced].1,
+            Err(_) => self.encoding.char_len(char),
+        }
+    }
+
+    /// Where in the input the character at `index` of the field starts
+    fn position_before(&self, index: usize) -> Position {
+        let (start, mut position) = self.field_positions[self
+            .field_positions
+            .partition_point(|(start, _)| *start <= index)
+            - 1];
+        for (offset, char) in self.field[start..index].char_indices() {
+            position.advance(char, self.char_len(start + offset, char));
+        }
+        position
+    }
+
+    /// Checks the field against the filters on its column, and reports it if
+    /// it isn't a value they can compare.
+    fn apply_filters(&mut self) {
+        let mut unparseable = false;
+        for (index, filter) in self.filters.iter().enumerate() {
+            if self.filter_columns[index] != self.column {
+                continue;
+            }
+            match filter.range.contains(&self.field, self.decimal_separator) {
+                Some(met) => self.filter_met[index] = met,
+                None => unparseable = true,
+            }
+        }
+
+        if unparseable {
+            self.unparseable_values.push(UnparseableValue {
+                path: self.path.clone(),
+                line_number: self.field_positions[0].1.line_number,
+                record_number: self.record_number,
+                column_number: self.column as u64 + 1,
+                column_name: self.header.get(self.column).cloned(),
+                value: Arc::from(self.field.as_str()),
+            });
+        }
+    }
+
+    /// Reports the record if it passed the filters and matches any of the
+    /// queries, or there are none, unless it's an empty line.
+    fn match_record(&mut self, passed: bool, occurences: &mut VecDeque<Occurence>) {
+        let record = mem::take(&mut self.record);
+        if passed && !matches!(record.as_slice(), [field] if field.is_empty()) {
+            let matching = if self.queries.is_empty() {
+                vec![0]
+            } else {
+                self.queries
+                    .iter_mut()
+                    .zip(&self.query_columns)
+                    .enumerate()
+                    .filter_map(|(term, (query, columns))| {
+                        query.matches(&record, columns).then_some(term)
+                    })
+                    .collect::<Vec<_>>()
+            };
+            for term in matching {
+                occurences.push_back(self.record_occurence(term, &record));
+            }
+        }
+        self.record = record;
+        self.record.clear();
+    }
+
+    /// The record as a match of a query or the filters, from its first character to the
+    /// last one of its last field, with the fields joined by the separator
+    fn record_occurence(&self, term: usize, record: &[String]) -> Occurence {
+        let matched = record.join(self.seperator.encode_utf8(&mut [0; 4]));
+        let end = self.record_end;
+
+        Occurence {
+            path: self.path.clone(),
+            term,
+            record_number: self.record_number,
+            column_number: 0,
+            column_name: None,
+            line_number: end.line_number,
+            line_character_offset: end.line_character_offset,
+            line_byte_offset: end.line_byte_offset,
+            total_byte_offset: end.total_byte_offset,
+            match_length: matched.chars().count() as u64,
+            match_byte_length: end.total_byte_offset - self.record_start.total_byte_offset,
+            matched: Arc::from(matched),
+            distance: 0,
+        }
+    }
+
+    fn occurence(&self, found: Match) -> Occurence {
+        let matched = &self.field[found.range.clone()];
+        let last_char = matched.chars().next_back().unwrap();
+
+        let start = self.position_before(found.range.start);
+        let last_index = found.range.end - last_char.len_utf8();
+        let mut end = self.position_before(last_index);
+        end.advance(last_char, self.char_len(last_index, last_char));
+
+        Occurence {
+            path: self.path.clone(),
+            term: found.term,
+            record_number: self.record_number,
+            column_number: self.column as u64 + 1,
+            column_name: self.header.get(self.column).cloned(),
+            line_number: end.line_number,
+            line_character_offset: end.line_character_offset,
+            line_byte_offset: end.line_byte_offset,
+            total_byte_offset: end.total_byte_offset,
+            match_length: matched.chars().count() as u64,
+            match_byte_length: end.total_byte_offset - start.total_byte_offset,
+            matched: Arc::from(matched),
+            distance: found.distance,
+        }
+    }
+}
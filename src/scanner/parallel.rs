@@ -0,0 +1,388 @@
+use std::{fs::File, num::NonZero, panic, path::Path, sync::Arc, thread};
+
+use memchr::memchr;
+use tokio::sync::mpsc;
+
+use super::{
+    InvalidSequence, Occurence, Progress, Scan, ScanError, ScanState, ScanUpdate, Scanner, Step,
+    UnparseableValue,
+};
+use crate::{
+    archive::Archive,
+    decompress::{self, Compression},
+    encoding::Encoding,
+};
+
+/// Files are split into chunks of about this size, and only files of at
+/// least two chunks are scanned in parallel
+const CHUNK_SIZE: u64 = 32 * 1024 * 1024;
+
+type ChunkScan<'a> = Scan<&'a [u8]>;
+
+/// What was found in a chunk, with line and record numbers counted from
+/// wherever its scan started
+struct Chunk<'a> {
+    scan: ChunkScan<'a>,
+    occurences: Vec<Occurence>,
+    invalid_sequences: Vec<InvalidSequence>,
+    unparseable_values: Vec<UnparseableValue>,
+    error: Option<ScanError>,
+}
+
+impl<'a> Chunk<'a> {
+    /// Scans until `end`, which is `u64::MAX` for the last chunk.
+    fn scan(mut scan: ChunkScan<'a>, end: u64) -> Self {
+        let mut occurences = Vec::new();
+        let error = loop {
+            match scan.step(end) {
+                Ok(Step::Found(occurence)) => occurences.push(occurence),
+                Ok(Step::Paused | Step::Finished) => break None,
+                Err(err) => break Some(err),
+            }
+        };
+
+        Self {
+            invalid_sequences: scan.take_invalid_sequences(),
+            unparseable_values: scan.take_unparseable_values(),
+            scan,
+            occurences,
+            error,
+        }
+    }
+}
+
+/// What the line and record numbers of a chunk scan are off by
+#[derive(Debug, Clone, Copy, Default)]
+struct Offsets {
+    lines: u64,
+    records: u64,
+}
+
+impl Offsets {
+    /// The offsets for the chunk starting where `state` stopped.
+    fn after(self, state: &ScanState) -> Self {
+        Self {
+            lines: self.lines + state.position.line_number - 1,
+            records: self.records + state.record_number - FIRST_RECORD,
+        }
+    }
+}
+
+/// Chunks but the first start counting records here, so they are never
+/// taken for the header
+const FIRST_RECORD: u64 = 2;
+
+/// What the scans of later chunks take over from the first one
+struct Resume {
+    encoding: Encoding,
+    fallback: bool,
+    // Where detected UTF-8 turns into Windows-1252, if it does
+    invalid_from: Option<u64>,
+    header: Vec<Arc<str>>,
+    selected: Option<Vec<bool>>,
+    query_columns: Vec<Vec<usize>>,
+    filter_columns: Vec<usize>,
+}
+
+impl Scanner {
+    /// Scans a large plain file in chunks on every core and sends the
+    /// updates in file order, `None` if the file isn't one.
+    pub(super) fn stream_parallel(
+        &self,
+        path: &Path,
+        progress: &mut Progress,
+        sender: &mpsc::Sender<Result<ScanUpdate, ScanError>>,
+    ) -> Option<Result<(), ()>> {
+        // errors are reported by the sequential scan
+        let file = File::open(path).ok()?;
+        let map = decompress::map(&file)?;
+
+        let encoding = match self.config.encoding {
+            Encoding::Auto => Encoding::detect(&map),
+            encoding => encoding,
+        };
+        // a line break is a single byte that can't be part of a character
+        let splittable = matches!(
+            encoding,
+            Encoding::Utf8 | Encoding::Windows1252 | Encoding::Latin1
+        );
+
+        if (map.len() as u64) < 2 * CHUNK_SIZE
+            || !splittable
+            || Compression::detect(&map).is_some()
+            || Archive::detect(&map)
+        {
+            return None;
+        }
+
+        Some(self.stream_chunks(Arc::from(path), &map, CHUNK_SIZE, progress, sender))
+    }
+
+    fn stream_chunks(
+        &self,
+        path: Arc<Path>,
+        map: &[u8],
+        chunk_size: u64,
+        progress: &mut Progress,
+        sender: &mpsc::Sender<Result<ScanUpdate, ScanError>>,
+    ) -> Result<(), ()> {
+        let send = |result| sender.blocking_send(result).map_err(|_| ());
+        // the progress doesn't include this file until it's done
+        let update = |progress: &mut Progress,
+                      scanned,
+                      done,
+                      occurences,
+                      invalid_sequences,
+                      unparseable_values| {
+            send(Ok(ScanUpdate {
+                path: path.clone(),
+                file_scanned: scanned,
+                files_scanned: progress.files + u64::from(done),
+                now_scanned: progress.bytes + scanned,
+                now_read: progress.read + scanned,
+                occurences,
+                invalid_sequences,
+                unparseable_values,
+            }))?;
+            if done {
+                progress.add(scanned, scanned);
+            }
+            Ok(())
+        };
+        let threads = thread::available_parallelism().map_or(1, NonZero::get);
+
+        // the header is needed to scan any other chunk
+        let mut first = self.scan_buffered(map, Some(path.clone()));
+        if let Err(err) = first.read_header() {
+            let invalid_sequences = first.take_invalid_sequences();
+            let unparseable_values = first.take_unparseable_values();
+            update(
+                progress,
+                first.bytes_scanned(),
+                true,
+                Vec::new(),
+                invalid_sequences,
+                unparseable_values,
+            )?;
+            return send(Err(ScanError::in_file(path.as_ref(), err)));
+        }
+        let resume = Resume {
+            encoding: first.state.encoding,
+            fallback: first.state.fallback,
+            // the chunks after that are scanned in Windows-1252 right away
+            invalid_from: first
+                .state
+                .fallback
+                .then(|| std::str::from_utf8(map).err())
+                .flatten()
+                .map(|err| err.valid_up_to() as u64),
+            header: first.state.header.clone(),
+            selected: first.state.selected.clone(),
+            query_columns: first.state.query_columns.clone(),
+            filter_columns: first.state.filter_columns.clone(),
+        };
+
+        let starts = chunk_starts(map, first.bytes_scanned(), chunk_size);
+        let ends = starts.iter().skip(1).copied().chain([u64::MAX]);
+        let mut chunks = starts.iter().copied().zip(ends);
+        let mut first = Some(first);
+
+        // The scan of the chunk merged last, and what its numbers are off by
+        let mut last: Option<(ChunkScan<'_>, Offsets)> = None;
+
+        loop {
+            let round = chunks
+                .by_ref()
+                .take(threads)
+                .map(|(start, end)| match first.take() {
+                    Some(first) => (first, end),
+                    None => (self.chunk_scan(map, start, &path, &resume), end),
+                })
+                .collect::<Vec<_>>();
+            if round.is_empty() {
+                break;
+            }
+
+            let ends = round.iter().map(|(_, end)| *end).collect::<Vec<_>>();
+            let results = thread::scope(|scope| {
+                let handles = round
+                    .into_iter()
+                    .map(|(scan, end)| scope.spawn(move || Chunk::scan(scan, end)))
+                    .collect::<Vec<_>>();
+                handles
+                    .into_iter()
+                    .map(|handle| {
+                        handle
+                            .join()
+                            .unwrap_or_else(|err| panic::resume_unwind(err))
+                    })
+                    .collect::<Vec<_>>()
+            });
+
+            for (chunk, end) in results.into_iter().zip(ends) {
+                let (chunk, offsets) = match last.take() {
+                    None => (chunk, Offsets::default()),
+                    Some((scan, offsets)) if scan.state.at_record_start() => {
+                        (chunk, offsets.after(&scan.state))
+                    }
+                    // a quoted field goes on over the line break this chunk
+                    // was started after, so the last scan goes on instead
+                    Some((scan, offsets)) => (Chunk::scan(scan, end), offsets),
+                };
+
+                let Chunk {
+                    scan,
+                    mut occurences,
+                    mut invalid_sequences,
+                    mut unparseable_values,
+                    error,
+                } = chunk;
+                for occurence in &mut occurences {
+                    occurence.line_number += offsets.lines;
+                    occurence.record_number += offsets.records;
+                }
+                for sequence in &mut invalid_sequences {
+                    sequence.line_number += offsets.lines;
+                }
+                for value in &mut unparseable_values {
+                    value.line_number += offsets.lines;
+                    value.record_number += offsets.records;
+                }
+
+                let done = error.is_some() || end == u64::MAX;
+                update(
+                    progress,
+                    scan.bytes_scanned(),
+                    done,
+                    occurences,
+                    invalid_sequences,
+                    unparseable_values,
+                )?;
+                if let Some(err) = error {
+                    return send(Err(ScanError::in_file(path.as_ref(), err)));
+                }
+                last = Some((scan, offsets));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// A scan of the chunk starting at `start`, right after a line break.
+    fn chunk_scan<'a>(
+        &self,
+        map: &'a [u8],
+        start: u64,
+        path: &Arc<Path>,
+        resume: &Resume,
+    ) -> ChunkScan<'a> {
+        let mut state = ScanState::new(self, Some(path.clone()));
+        state.encoding = resume.encoding;
+        state.fallback = resume.fallback;
+        if resume.invalid_from.is_some_and(|from| from < start) {
+            state.encoding = Encoding::Windows1252;
+            state.fallback = false;
+        }
+        state.header = resume.header.clone();
+        state.selected = resume.selected.clone();
+        state.query_columns = resume.query_columns.clone();
+        state.filter_columns = resume.filter_columns.clone();
+        state.record_number = FIRST_RECORD;
+        // byte offsets count from the start of the file right away, line
+        // and record numbers once the previous chunks are done
+        state.position.total_byte_offset = start;
+        state.field_positions = vec![(0, state.position)];
+
+        // the rest of the file, in case the next chunk didn't start a record
+        Scan::new(&map[start as usize..], state)
+    }
+}
+
+/// Where the chunks start, the first one at the start of the file and every
+/// other one after a line break about `chunk_size` bytes on and past
+/// `header_end`.
+fn chunk_starts(map: &[u8], header_end: u64, chunk_size: u64) -> Vec<u64> {
+    let mut starts = vec![0];
+    let mut from = chunk_size.max(header_end);
+
+    while let Some(newline) = map
+        .get(from as usize..)
+        .and_then(|rest| memchr(b'\n', rest))
+    {
+        let start = from + newline as u64 + 1;
+        if start >= map.len() as u64 {
+            break;
+        }
+        starts.push(start);
+        from = start + chunk_size;
+    }
+
+    starts
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection::vec, prelude::*};
+
+    use super::*;
+    use crate::scanner::ScanConfig;
+
+    /// The occurences found in the input scanned in chunks of about
+    /// `chunk_size` bytes
+    fn scan_chunks(scanner: &Scanner, input: &[u8], chunk_size: u64) -> Vec<Occurence> {
+        let path = Arc::from(Path::new("input.csv"));
+        let (sender, mut receiver) = mpsc::channel(16);
+        thread::scope(|scope| {
+            scope.spawn(move || {
+                let mut progress = Progress::default();
+                scanner.stream_chunks(path, input, chunk_size, &mut progress, &sender)
+            });
+            let mut occurences = Vec::new();
+            while let Some(update) = receiver.blocking_recv() {
+                occurences.extend(update.unwrap().occurences);
+            }
+            occurences
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn chunks(
+            term in "a|ab|a\nb",
+            records in vec("[ab,\"\r\n]{0,10}", 0..40),
+            chunk_size in 1..64u64,
+        ) {
+            let input = format!("x,y\n{}", records.join("\n"));
+            let scanner = Scanner::new(ScanConfig::new(term)).unwrap();
+            let sequential = scanner
+                .scan_named(input.as_bytes(), Some(Arc::from(Path::new("input.csv"))))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            prop_assert_eq!(scan_chunks(&scanner, input.as_bytes(), chunk_size), sequential);
+        }
+
+        #[test]
+        fn windows_1252_chunks(
+            records in vec("[aü,\n]{0,10}", 1..40),
+            invalid in any::<prop::sample::Index>(),
+            chunk_size in 1..64u64,
+        ) {
+            // detected as UTF-8 up to a byte which isn't, and Windows-1252
+            // from there on, where "ü" in UTF-8 is "Ã¼"
+            let body = records.join("\n");
+            let boundaries = body
+                .char_indices()
+                .map(|(index, _)| index)
+                .chain([body.len()])
+                .collect::<Vec<_>>();
+            let mut input = format!("x,y\n{}", body).into_bytes();
+            input.insert(4 + invalid.get(&boundaries), 0xfc);
+            let scanner = Scanner::new(ScanConfig::new("ü")).unwrap();
+            let sequential = scanner
+                .scan_named(&input[..], Some(Arc::from(Path::new("input.csv"))))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            prop_assert_eq!(scan_chunks(&scanner, &input, chunk_size), sequential);
+        }
+    }
+}
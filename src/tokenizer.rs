@@ -0,0 +1,113 @@
+/// What a character of the input means for the CSV structure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Token {
+    /// A character of the current field's value
+    Char(char),
+    /// A character that's part of the syntax, like an enclosing quote
+    Skip,
+    /// A carriage return outside of quotes, which belongs to the line break
+    /// if a line feed follows, and to the field otherwise
+    CarriageReturn,
+    /// The separator between two fields
+    FieldEnd,
+    /// The line break at the end of a record
+    RecordEnd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    FieldStart,
+    Unquoted,
+    Quoted,
+    // A quote inside a quoted field, either closing it or escaping another one
+    QuoteInQuoted,
+}
+
+/// Splits CSV input as described in RFC 4180, fed one character at a time.
+///
+/// Quoted fields may contain separators, line breaks and doubled quotes.
+/// Malformed input is read leniently: quotes inside an unquoted field and
+/// characters after a closing quote are taken as they are.
+#[derive(Debug, Clone)]
+pub(crate) struct Tokenizer {
+    seperator: char,
+    quote: Option<char>,
+    state: State,
+    // Whether the last character was a carriage return outside of quotes
+    carriage_return: bool,
+}
+
+impl Tokenizer {
+    pub(crate) fn new(seperator: char, quote: Option<char>) -> Self {
+        Self {
+            seperator,
+            quote,
+            state: State::FieldStart,
+            carriage_return: false,
+        }
+    }
+
+    pub(crate) fn push(&mut self, char: char) -> Token {
+        let is_quote = Some(char) == self.quote;
+        self.carriage_return = false;
+
+        match self.state {
+            State::Quoted => {
+                if is_quote {
+                    self.state = State::QuoteInQuoted;
+                    Token::Skip
+                } else {
+                    Token::Char(char)
+                }
+            }
+            State::QuoteInQuoted if is_quote => {
+                self.state = State::Quoted;
+                Token::Char(char)
+            }
+            State::FieldStart if is_quote => {
+                self.state = State::Quoted;
+                Token::Skip
+            }
+            State::FieldStart | State::Unquoted | State::QuoteInQuoted => {
+                if char == self.seperator {
+                    self.state = State::FieldStart;
+                    Token::FieldEnd
+                } else if char == '\n' {
+                    self.state = State::FieldStart;
+                    Token::RecordEnd
+                } else if char == '\r' {
+                    // a quote after it doesn't start a quoted field
+                    if self.state == State::FieldStart {
+                        self.state = State::Unquoted;
+                    }
+                    self.carriage_return = true;
+                    Token::CarriageReturn
+                } else {
+                    self.state = State::Unquoted;
+                    Token::Char(char)
+                }
+            }
+        }
+    }
+
+    /// Whether the next character starts a field
+    pub(crate) fn at_field_start(&self) -> bool {
+        self.state == State::FieldStart
+    }
+
+    /// How many bytes at the start of `text` are characters of the current
+    /// field which leave the state as it is, so they can be taken at once.
+    pub(crate) fn run_len(&self, text: &str) -> usize {
+        if self.carriage_return {
+            // the next character decides what the carriage return is
+            return 0;
+        }
+        let end = match self.state {
+            State::Unquoted => text.find([self.seperator, '\n', '\r']),
+            State::Quoted => self.quote.and_then(|quote| text.find(quote)),
+            // the next character decides how the field goes on
+            State::FieldStart | State::QuoteInQuoted => return 0,
+        };
+        end.unwrap_or(text.len())
+    }
+}
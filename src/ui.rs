@@ -1,30 +1,68 @@
-use std::{collections::VecDeque, mem, path::PathBuf, sync::Arc, time::Instant};
+use std::{
+    collections::{BTreeMap, HashSet},
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::Instant,
+};
 
+use csv_searcher::{
+    encoding::Encoding,
+    export,
+    scanner::{
+        CaseMode, Column, DateFormat, DecimalSeparator, Filter, InvalidSequence, MatchScope,
+        Normalization, Occurence, Phonetic, ScanConfig, ScanError, ScanUpdate, Scanner, SearchMode,
+        UnparseableValue,
+    },
+};
 use iced::{
     Length::{self},
     Task,
     alignment::Vertical,
+    futures::StreamExt,
     task::sipper,
-    widget::{button, column, row, scrollable, text, text_input},
+    widget::{button, checkbox, column, pick_list, row, scrollable, text, text_editor, text_input},
 };
 use rfd::{AsyncFileDialog, FileHandle};
-use tokio::io::{AsyncReadExt, BufReader};
+use tokio::sync::oneshot;
 use tokio_util::sync::CancellationToken;
 
 #[derive(Debug, Clone)]
 pub enum Message {
     SelectFile,
+    SelectFolder,
     SelectedFolder(Option<Arc<FileHandle>>),
     AbortScan,
     ScanComplete,
     Error(String),
     SearchChanged(String),
+    MultipleTermsToggled(bool),
+    SearchTermsEdited(text_editor::Action),
+    ModeSelected(SearchMode),
+    MaxDistanceChanged(String),
+    PhoneticSelected(Phonetic),
+    CaseSelected(CaseMode),
+    NormalizationSelected(Normalization),
+    IgnoreDiacriticsToggled(bool),
+    ScopeSelected(MatchScope),
+    TrimToggled(bool),
+    OverlappingToggled(bool),
     SeperatorChanged(String),
+    QuoteChanged(String),
+    HasHeaderToggled(bool),
+    EncodingSelected(Encoding),
+    LossyToggled(bool),
+    FilePatternsChanged(String),
+    MemberPatternsChanged(String),
+    // The number of the request and the header it read
+    HeaderRead(u64, Result<Vec<Arc<str>>, String>),
+    ColumnToggled(usize, bool),
+    FiltersChanged(String),
+    DecimalSeparatorSelected(DecimalSeparator),
+    DateFormatChanged(String),
     StartScan,
-    ScanUpdate {
-        now_scanned: u64,
-        occurences: Vec<Occurence>,
-    },
+    ScanUpdate(ScanUpdate),
     ExportCsv,
     CsvExportComplete(Result<String, String>),
 }
@@ -35,10 +73,56 @@ pub struct UI {
     cancellation_token: Option<CancellationToken>,
     paths_over_limit: Vec<Occurence>,
     scanned: u64,
+    // Bytes read from disk, fewer than scanned for compressed files
+    read: u64,
     search_string: String,
-    running_search_string: String,
+    multiple_terms: bool,
+    search_terms_content: text_editor::Content,
+    search_terms: Vec<String>,
+    running_search_terms: Vec<String>,
+    term_hits: Vec<u64>,
+    mode: SearchMode,
+    max_distance: u32,
+    phonetic: Phonetic,
+    case: CaseMode,
+    normalization: Normalization,
+    ignore_diacritics: bool,
+    scope: MatchScope,
+    trim: bool,
+    overlapping: bool,
+    search_error: Option<String>,
     seperator: char,
     running_seperator: char,
+    quote: Option<char>,
+    has_header: bool,
+    encoding: Encoding,
+    lossy: bool,
+    invalid_sequences: Vec<InvalidSequence>,
+    // The values of the first record and whether their column is searched
+    columns: Vec<(Arc<str>, bool)>,
+    // Counts the headers read, so only the latest one is shown
+    header_request: u64,
+    columns_error: Option<String>,
+    // Filters on the values of columns, separated by semicolons
+    filters: String,
+    filters_error: Option<String>,
+    decimal_separator: DecimalSeparator,
+    // How dates in filtered columns are written, empty to detect it
+    date_format: String,
+    unparseable_values: Vec<UnparseableValue>,
+    // Globs of the files to scan in a folder, separated by whitespace
+    file_patterns: String,
+    // Globs of the members to scan in an archive
+    member_patterns: String,
+    current_file: Option<Arc<Path>>,
+    files_scanned: u64,
+    file_hits: BTreeMap<Arc<Path>, u64>,
+    // Hits and header name by column number
+    column_hits: BTreeMap<u64, (Option<Arc<str>>, u64)>,
+    // Hits by how far they are from the term in a fuzzy search
+    distance_hits: BTreeMap<u32, u64>,
+    // Hits by the value matched, which may differ from the term
+    value_hits: BTreeMap<Arc<str>, u64>,
     start_time: Instant,
     end_time: Option<Instant>,
     errors: Vec<String>,
@@ -47,14 +131,6 @@ pub struct UI {
     export_success: bool,
 }
 
-#[derive(Debug, Clone)]
-pub struct Occurence {
-    line_number: u64,
-    line_character_offset: u64,
-    line_byte_offset: u64,
-    total_byte_offset: u64,
-}
-
 impl UI {
     pub fn start() -> (Self, Task<Message>) {
         (
@@ -64,10 +140,46 @@ impl UI {
                 cancellation_token: None,
                 paths_over_limit: Vec::new(),
                 scanned: 0,
+                read: 0,
                 search_string: String::new(),
-                running_search_string: String::new(),
+                multiple_terms: false,
+                search_terms_content: text_editor::Content::new(),
+                search_terms: Vec::new(),
+                running_search_terms: Vec::new(),
+                term_hits: Vec::new(),
+                mode: SearchMode::default(),
+                max_distance: 1,
+                phonetic: Phonetic::default(),
+                case: CaseMode::default(),
+                normalization: Normalization::default(),
+                ignore_diacritics: false,
+                scope: MatchScope::default(),
+                trim: false,
+                overlapping: true,
+                search_error: None,
                 seperator: ',',
                 running_seperator: ',',
+                quote: Some('"'),
+                has_header: true,
+                encoding: Encoding::default(),
+                lossy: false,
+                invalid_sequences: Vec::new(),
+                columns: Vec::new(),
+                header_request: 0,
+                columns_error: None,
+                filters: String::new(),
+                filters_error: None,
+                decimal_separator: DecimalSeparator::default(),
+                date_format: String::new(),
+                unparseable_values: Vec::new(),
+                file_patterns: String::new(),
+                member_patterns: String::new(),
+                current_file: None,
+                files_scanned: 0,
+                file_hits: BTreeMap::new(),
+                column_hits: BTreeMap::new(),
+                distance_hits: BTreeMap::new(),
+                value_hits: BTreeMap::new(),
                 errors: Vec::new(),
                 exporting: false,
                 export_message: None,
@@ -84,17 +196,25 @@ impl UI {
             Message::SelectFile => {
                 self.selecting = true;
                 Task::future(async {
-                    let folder = AsyncFileDialog::new().pick_file().await;
+                    let file = AsyncFileDialog::new().pick_file().await;
+                    Message::SelectedFolder(file.map(Arc::new))
+                })
+            }
+            Message::SelectFolder => {
+                self.selecting = true;
+                Task::future(async {
+                    let folder = AsyncFileDialog::new().pick_folder().await;
                     Message::SelectedFolder(folder.map(Arc::new))
                 })
             }
             Message::SelectedFolder(selected) => {
                 self.selecting = false;
-                if let Some(selected) = selected {
-                    if let Some(selected) = Arc::into_inner(selected) {
-                        let selected: PathBuf = selected.path().into();
-                        self.selected = Some(selected.clone());
-                    }
+                if let Some(selected) = selected
+                    && let Some(selected) = Arc::into_inner(selected)
+                {
+                    let selected: PathBuf = selected.path().into();
+                    self.selected = Some(selected.clone());
+                    return self.read_header();
                 }
                 Task::none()
             }
@@ -117,45 +237,234 @@ impl UI {
             }
             Message::SearchChanged(new_search) => {
                 self.search_string = new_search.clone();
+                self.validate_search();
+                Task::none()
+            }
+            Message::MultipleTermsToggled(multiple_terms) => {
+                self.multiple_terms = multiple_terms;
+                self.validate_search();
+                Task::none()
+            }
+            Message::SearchTermsEdited(action) => {
+                let is_edit = action.is_edit();
+                self.search_terms_content.perform(action);
+                if is_edit {
+                    // one term per line
+                    self.search_terms = self
+                        .search_terms_content
+                        .text()
+                        .lines()
+                        .map(str::trim)
+                        .filter(|term| !term.is_empty())
+                        .map(ToString::to_string)
+                        .collect();
+                    self.validate_search();
+                }
+                Task::none()
+            }
+            Message::ModeSelected(mode) => {
+                self.mode = mode;
+                self.validate_search();
+                Task::none()
+            }
+            Message::MaxDistanceChanged(max_distance) => {
+                if max_distance.is_empty() {
+                    self.max_distance = 0;
+                } else if let Ok(max_distance) = max_distance.parse() {
+                    self.max_distance = max_distance;
+                }
+                Task::none()
+            }
+            Message::PhoneticSelected(phonetic) => {
+                self.phonetic = phonetic;
+                self.validate_search();
+                Task::none()
+            }
+            Message::CaseSelected(case) => {
+                self.case = case;
+                self.validate_search();
+                Task::none()
+            }
+            Message::NormalizationSelected(normalization) => {
+                self.normalization = normalization;
+                self.validate_search();
+                Task::none()
+            }
+            Message::IgnoreDiacriticsToggled(ignore_diacritics) => {
+                self.ignore_diacritics = ignore_diacritics;
+                self.validate_search();
+                Task::none()
+            }
+            Message::ScopeSelected(scope) => {
+                self.scope = scope;
+                Task::none()
+            }
+            Message::TrimToggled(trim) => {
+                self.trim = trim;
+                Task::none()
+            }
+            Message::OverlappingToggled(overlapping) => {
+                self.overlapping = overlapping;
                 Task::none()
             }
             Message::SeperatorChanged(new_seperator) => {
                 for new_seperator in new_seperator.chars() {
                     if self.seperator != new_seperator {
                         self.seperator = new_seperator;
-                        break;
+                        return self.read_header();
+                    }
+                }
+                Task::none()
+            }
+            Message::QuoteChanged(new_quote) => {
+                if new_quote.is_empty() {
+                    self.quote = None;
+                    return self.read_header();
+                }
+                for new_quote in new_quote.chars() {
+                    if self.quote != Some(new_quote) {
+                        self.quote = Some(new_quote);
+                        return self.read_header();
+                    }
+                }
+                Task::none()
+            }
+            Message::HasHeaderToggled(has_header) => {
+                self.has_header = has_header;
+                self.read_header()
+            }
+            Message::LossyToggled(lossy) => {
+                self.lossy = lossy;
+                Task::none()
+            }
+            Message::EncodingSelected(encoding) => {
+                self.encoding = encoding;
+                self.read_header()
+            }
+            Message::FilePatternsChanged(file_patterns) => {
+                self.file_patterns = file_patterns;
+                self.read_header()
+            }
+            Message::MemberPatternsChanged(member_patterns) => {
+                self.member_patterns = member_patterns;
+                self.read_header()
+            }
+            Message::HeaderRead(request, result) => {
+                // a newer request has been made in the meantime
+                if request != self.header_request {
+                    return Task::none();
+                }
+                match result {
+                    Ok(header) => {
+                        // columns stay selected by their name
+                        let selected = self
+                            .columns
+                            .iter()
+                            .filter(|(_, selected)| *selected)
+                            .map(|(name, _)| name.clone())
+                            .collect::<HashSet<_>>();
+                        self.columns = header
+                            .into_iter()
+                            .map(|name| {
+                                let is_selected = selected.contains(&name);
+                                (name, is_selected)
+                            })
+                            .collect();
+                        self.columns_error = None;
+                    }
+                    Err(err) => {
+                        self.columns.clear();
+                        self.columns_error = Some(err);
                     }
                 }
                 Task::none()
             }
+            Message::ColumnToggled(index, selected) => {
+                if let Some(column) = self.columns.get_mut(index) {
+                    column.1 = selected;
+                }
+                Task::none()
+            }
+            Message::FiltersChanged(filters) => {
+                self.filters = filters;
+                self.validate_filters();
+                Task::none()
+            }
+            Message::DecimalSeparatorSelected(decimal_separator) => {
+                self.decimal_separator = decimal_separator;
+                self.validate_filters();
+                self.validate_search();
+                Task::none()
+            }
+            Message::DateFormatChanged(date_format) => {
+                self.date_format = date_format;
+                self.validate_filters();
+                Task::none()
+            }
             Message::StartScan => {
                 if let Some(ref folder) = self.selected {
+                    let scanner = match Scanner::new(self.scan_config()) {
+                        Ok(scanner) => scanner,
+                        Err(err) => {
+                            self.search_error = Some(err.to_string());
+                            return Task::none();
+                        }
+                    };
                     self.paths_over_limit.clear();
                     self.errors.clear();
+                    self.invalid_sequences.clear();
+                    self.unparseable_values.clear();
                     self.scanned = 0;
+                    self.read = 0;
                     self.export_message = None;
                     let token = CancellationToken::new();
                     self.cancellation_token = Some(token.clone());
-                    self.running_search_string = self.search_string.clone();
-                    self.running_seperator = self.seperator.clone();
+                    self.running_search_terms = scanner.config().search_terms.clone();
+                    self.term_hits = vec![0; self.running_search_terms.len()];
+                    self.column_hits.clear();
+                    self.distance_hits.clear();
+                    self.value_hits.clear();
+                    self.file_hits.clear();
+                    self.current_file = None;
+                    self.files_scanned = 0;
+                    self.running_seperator = self.seperator;
                     self.start_time = Instant::now();
                     self.end_time = None;
-                    self.start_scan(
-                        folder.clone(),
-                        self.running_search_string.clone(),
-                        self.running_seperator.clone(),
-                        token,
-                    )
+                    self.start_scan(folder.clone(), scanner, token)
                 } else {
                     Task::none()
                 }
             }
-            Message::ScanUpdate {
-                now_scanned,
-                occurences: new_paths_over_limit,
-            } => {
-                self.scanned = now_scanned;
+            Message::ScanUpdate(update) => {
+                self.scanned = update.now_scanned;
+                self.read = update.now_read;
+                self.files_scanned = update.files_scanned;
+                if !update.occurences.is_empty() {
+                    *self.file_hits.entry(update.path.clone()).or_default() +=
+                        update.occurences.len() as u64;
+                }
+                self.current_file = Some(update.path);
+                let new_paths_over_limit = update.occurences;
+                for occurence in &new_paths_over_limit {
+                    self.term_hits[occurence.term] += 1;
+                    *self.distance_hits.entry(occurence.distance).or_default() += 1;
+                    // whole records matching a query are in no column and
+                    // hardly ever the same
+                    if occurence.column_number == 0 {
+                        continue;
+                    }
+                    self.column_hits
+                        .entry(occurence.column_number)
+                        .or_insert_with(|| (occurence.column_name.clone(), 0))
+                        .1 += 1;
+                    *self
+                        .value_hits
+                        .entry(occurence.matched.clone())
+                        .or_default() += 1;
+                }
                 self.paths_over_limit.extend(new_paths_over_limit);
+                self.invalid_sequences.extend(update.invalid_sequences);
+                self.unparseable_values.extend(update.unparseable_values);
                 Task::none()
             }
             Message::ExportCsv => {
@@ -165,6 +474,7 @@ impl UI {
                     self.exporting = true;
                     self.export_message = None;
                     let paths_to_export = self.paths_over_limit.clone();
+                    let search_terms = self.running_search_terms.clone();
                     Task::future(async move {
                         let file_handle = AsyncFileDialog::new()
                             .set_file_name("occurences.csv")
@@ -181,8 +491,9 @@ impl UI {
                                     use tokio::io::AsyncWriteExt;
 
                                     // Write CSV header
-                                    if let Err(e) =
-                                        file.write_all(b"Byte offset,Line,Char offset in line, Byte offset in line\n").await
+                                    if let Err(e) = file
+                                        .write_all(format!("{}\n", export::HEADER).as_bytes())
+                                        .await
                                     {
                                         return Message::CsvExportComplete(Err(format!(
                                             "Failed to write CSV header: {}",
@@ -194,13 +505,9 @@ impl UI {
                                     for chunk in paths_to_export.chunks(1000) {
                                         let mut chunk_content = String::new();
                                         for occurence in chunk {
-                                            chunk_content.push_str(&format!(
-                                                "{},{},{},{}\n",
-                                                occurence.total_byte_offset,
-                                                occurence.line_number,
-                                                occurence.line_character_offset,
-                                                occurence.line_byte_offset
-                                            ));
+                                            chunk_content
+                                                .push_str(&export::row(occurence, &search_terms));
+                                            chunk_content.push('\n');
                                         }
 
                                         if let Err(e) =
@@ -256,6 +563,107 @@ impl UI {
     }
 
     pub fn view(&'_ self) -> iced::Element<'_, Message> {
+        let mut search = column![if self.multiple_terms {
+            row![
+                text("Search Terms:").width(150),
+                text_editor(&self.search_terms_content)
+                    .placeholder("One term per line")
+                    .on_action(Message::SearchTermsEdited)
+                    .height(150),
+                pick_list(SearchMode::ALL, Some(self.mode), Message::ModeSelected),
+                checkbox(self.overlapping)
+                    .label("Overlapping")
+                    .on_toggle(Message::OverlappingToggled),
+            ]
+            .spacing(10)
+        } else {
+            row![
+                text("Search String:").width(150),
+                text_input("", &self.search_string)
+                    .on_input(Message::SearchChanged)
+                    .on_submit(Message::StartScan)
+                    .width(Length::Fill),
+                pick_list(SearchMode::ALL, Some(self.mode), Message::ModeSelected),
+                checkbox(self.overlapping)
+                    .label("Overlapping")
+                    .on_toggle(Message::OverlappingToggled),
+            ]
+            .spacing(10)
+            .align_y(Vertical::Center)
+        },]
+        .spacing(5);
+
+        if self.mode == SearchMode::Fuzzy {
+            search = search.push(
+                row![
+                    text("Max. distance:").width(150),
+                    text_input("", &self.max_distance.to_string())
+                        .on_input(Message::MaxDistanceChanged)
+                        .on_submit(Message::StartScan)
+                        .width(50),
+                ]
+                .spacing(10)
+                .align_y(Vertical::Center),
+            );
+        }
+
+        if self.mode == SearchMode::Phonetic {
+            search = search.push(
+                row![
+                    text("Sounds alike by:").width(150),
+                    pick_list(
+                        Phonetic::ALL,
+                        Some(self.phonetic),
+                        Message::PhoneticSelected
+                    ),
+                ]
+                .spacing(10)
+                .align_y(Vertical::Center),
+            );
+        }
+
+        if let Some(error) = &self.search_error {
+            search = search.push(row![
+                text("").width(150),
+                text(error)
+                    .size(14)
+                    .color(iced::Color::from_rgb(0.8, 0.2, 0.2)),
+            ]);
+        }
+
+        let mut filters = column![
+            row![
+                text("Filters:").width(150),
+                text_input("quantity < 0; price between 1000 and 5000", &self.filters)
+                    .on_input(Message::FiltersChanged)
+                    .on_submit(Message::StartScan)
+                    .width(Length::Fill),
+                text("Numbers like:"),
+                pick_list(
+                    DecimalSeparator::ALL,
+                    Some(self.decimal_separator),
+                    Message::DecimalSeparatorSelected
+                ),
+                text("Dates like:"),
+                text_input("auto", &self.date_format)
+                    .on_input(Message::DateFormatChanged)
+                    .on_submit(Message::StartScan)
+                    .width(150),
+            ]
+            .spacing(10)
+            .align_y(Vertical::Center),
+        ]
+        .spacing(5);
+
+        if let Some(error) = &self.filters_error {
+            filters = filters.push(row![
+                text("").width(150),
+                text(error)
+                    .size(14)
+                    .color(iced::Color::from_rgb(0.8, 0.2, 0.2)),
+            ]);
+        }
+
         let main_controls = column![
             row![
                 button(text("Select File")).on_press_maybe(if self.selecting {
@@ -263,6 +671,11 @@ impl UI {
                 } else {
                     Some(Message::SelectFile)
                 }),
+                button(text("Select Folder")).on_press_maybe(if self.selecting {
+                    None
+                } else {
+                    Some(Message::SelectFolder)
+                }),
                 if let Some(selected) = &self.selected {
                     text(selected.to_string_lossy())
                 } else {
@@ -271,12 +684,37 @@ impl UI {
             ]
             .spacing(10)
             .align_y(Vertical::Center),
+            search,
             row![
-                text("Search String:").width(150),
-                text_input("", &self.search_string)
-                    .on_input(Message::SearchChanged)
-                    .on_submit(Message::StartScan)
-                    .width(Length::Fill),
+                text("").width(150),
+                checkbox(self.multiple_terms)
+                    .label("Search for multiple terms")
+                    .on_toggle(Message::MultipleTermsToggled),
+            ]
+            .spacing(10),
+            row![
+                text("Case:").width(150),
+                pick_list(CaseMode::ALL, Some(self.case), Message::CaseSelected),
+                text("Normalization:"),
+                pick_list(
+                    Normalization::ALL,
+                    Some(self.normalization),
+                    Message::NormalizationSelected
+                ),
+                checkbox(self.ignore_diacritics)
+                    .label("Ignore diacritics")
+                    .on_toggle(Message::IgnoreDiacriticsToggled),
+            ]
+            .spacing(10)
+            .align_y(Vertical::Center),
+            row![
+                text("Match:").width(150),
+                pick_list(MatchScope::ALL, Some(self.scope), Message::ScopeSelected),
+                checkbox(self.trim)
+                    .label("Ignore whitespace around fields")
+                    .on_toggle_maybe(
+                        (self.scope == MatchScope::Field).then_some(Message::TrimToggled)
+                    ),
             ]
             .spacing(10)
             .align_y(Vertical::Center),
@@ -289,11 +727,62 @@ impl UI {
             ]
             .spacing(10)
             .align_y(Vertical::Center),
+            row![
+                text("Quote:").width(150),
+                text_input("", &self.quote.map(String::from).unwrap_or_default())
+                    .on_input(Message::QuoteChanged)
+                    .on_submit(Message::StartScan)
+                    .width(50),
+            ]
+            .spacing(10)
+            .align_y(Vertical::Center),
+            row![
+                text("").width(150),
+                checkbox(self.has_header)
+                    .label("First line contains the column names")
+                    .on_toggle(Message::HasHeaderToggled),
+            ]
+            .spacing(10),
+            row![
+                text("Encoding:").width(150),
+                pick_list(
+                    Encoding::ALL,
+                    Some(self.encoding),
+                    Message::EncodingSelected
+                ),
+                checkbox(self.lossy)
+                    .label("Replace invalid characters instead of stopping")
+                    .on_toggle(Message::LossyToggled),
+            ]
+            .spacing(10)
+            .align_y(Vertical::Center),
+            row![
+                text("Files in folder:").width(150),
+                text_input("*.csv !archive/**", &self.file_patterns)
+                    .on_input(Message::FilePatternsChanged)
+                    .on_submit(Message::StartScan)
+                    .width(Length::Fill),
+            ]
+            .spacing(10)
+            .align_y(Vertical::Center),
+            row![
+                text("Files in archive:").width(150),
+                text_input("*.csv *.tsv *.txt", &self.member_patterns)
+                    .on_input(Message::MemberPatternsChanged)
+                    .on_submit(Message::StartScan)
+                    .width(Length::Fill),
+            ]
+            .spacing(10)
+            .align_y(Vertical::Center),
+            row![text("Columns:").width(150), self.column_selection()].spacing(10),
+            filters,
             row![
                 button(text("Start Scan")).on_press_maybe(
                     if self.selected.is_some()
-                        && !self.cancellation_token.is_some()
-                        && !self.search_string.is_empty()
+                        && self.cancellation_token.is_none()
+                        && self.has_search_terms()
+                        && self.search_error.is_none()
+                        && self.filters_error.is_none()
                     {
                         Some(Message::StartScan)
                     } else {
@@ -325,8 +814,13 @@ impl UI {
         if self.cancellation_token.is_some() {
             content = content.push(
                 text(format!(
-                    "Scanning... {} bytes searched in {:.2} seconds",
-                    self.scanned,
+                    "Scanning {}... {} files and {} searched in {:.2} seconds",
+                    self.current_file
+                        .as_deref()
+                        .map(|path| path.to_string_lossy())
+                        .unwrap_or_default(),
+                    self.files_scanned,
+                    self.bytes_scanned(),
                     Instant::now().duration_since(self.start_time).as_millis() as f64 / 1000.0
                 ))
                 .size(16),
@@ -334,8 +828,9 @@ impl UI {
         } else if let Some(end) = self.end_time {
             content = content.push(
                 text(format!(
-                    "Scanned {} bytes in {:.2} seconds",
-                    self.scanned,
+                    "Scanned {} files and {} in {:.2} seconds",
+                    self.files_scanned,
+                    self.bytes_scanned(),
                     end.duration_since(self.start_time).as_millis() as f64 / 1000.0
                 ))
                 .size(16),
@@ -343,14 +838,111 @@ impl UI {
         }
 
         if !self.paths_over_limit.is_empty() {
-            let results_title = text(format!(
-                "Found {} occurences of \"{}\"",
-                self.paths_over_limit.len(),
-                self.running_search_string
-            ))
+            let results_title = if let [search_string] = self.running_search_terms.as_slice()
+                && search_string.is_empty()
+            {
+                text(format!(
+                    "Found {} records passing the filters",
+                    self.paths_over_limit.len()
+                ))
+            } else if let [search_string] = self.running_search_terms.as_slice() {
+                text(format!(
+                    "Found {} occurences of \"{}\"",
+                    self.paths_over_limit.len(),
+                    search_string
+                ))
+            } else {
+                text(format!(
+                    "Found {} occurences of {} terms",
+                    self.paths_over_limit.len(),
+                    self.running_search_terms.len()
+                ))
+            }
             .size(18);
 
-            content = content.push(results_title);
+            let mut summaries = row![].spacing(20);
+
+            if self.running_search_terms.len() > 1 {
+                let mut hits = self
+                    .running_search_terms
+                    .iter()
+                    .zip(&self.term_hits)
+                    .filter(|(_, hits)| **hits > 0)
+                    .collect::<Vec<_>>();
+                hits.sort_by(|a, b| b.1.cmp(a.1));
+
+                let hits_list = scrollable(column(
+                    hits.into_iter()
+                        .map(|(term, hits)| text(format!("{}: {}", term, hits)).into()),
+                ))
+                .height(Length::Fill)
+                .width(Length::Fill);
+
+                summaries = summaries.push(column![text("Hits per term").size(16), hits_list]);
+            }
+
+            if !self.column_hits.is_empty() {
+                let column_list = scrollable(column(self.column_hits.iter().map(
+                    |(column_number, (column_name, hits))| {
+                        text(format!(
+                            "{} {}: {}",
+                            column_number,
+                            column_name.as_deref().unwrap_or_default(),
+                            hits
+                        ))
+                        .into()
+                    },
+                )))
+                .height(Length::Fill)
+                .width(Length::Fill);
+
+                summaries = summaries.push(column![text("Hits per column").size(16), column_list]);
+            }
+
+            if self.distance_hits.keys().any(|distance| *distance > 0) {
+                let distance_list =
+                    scrollable(column(self.distance_hits.iter().map(|(distance, hits)| {
+                        text(format!("{}: {}", distance, hits)).into()
+                    })))
+                    .height(Length::Fill)
+                    .width(Length::Fill);
+
+                summaries =
+                    summaries.push(column![text("Hits per distance").size(16), distance_list]);
+            }
+
+            // only if something else was found than the terms themselves
+            if self.value_hits.keys().any(|value| {
+                !self
+                    .running_search_terms
+                    .iter()
+                    .any(|term| term.as_str() == value.as_ref())
+            }) {
+                let mut hits = self.value_hits.iter().collect::<Vec<_>>();
+                hits.sort_by(|a, b| b.1.cmp(a.1));
+
+                let value_list = scrollable(column(
+                    hits.into_iter()
+                        .map(|(value, hits)| text(format!("{}: {}", value, hits)).into()),
+                ))
+                .height(Length::Fill)
+                .width(Length::Fill);
+
+                summaries = summaries.push(column![text("Hits per value").size(16), value_list]);
+            }
+
+            if self.file_hits.len() > 1 {
+                let file_list =
+                    scrollable(column(self.file_hits.iter().map(|(path, hits)| {
+                        text(format!("{}: {}", path.display(), hits)).into()
+                    })))
+                    .height(Length::Fill)
+                    .width(Length::Fill);
+
+                summaries = summaries.push(column![text("Hits per file").size(16), file_list]);
+            }
+
+            content = content.push(results_title).push(summaries);
         }
 
         if self.exporting {
@@ -383,174 +975,253 @@ impl UI {
             content = content.push(errors_title).push(errors_list);
         }
 
+        if !self.invalid_sequences.is_empty() {
+            let invalid_title = text(format!(
+                "Replaced invalid characters ({})",
+                self.invalid_sequences.len()
+            ))
+            .size(18)
+            .color(iced::Color::from_rgb(0.8, 0.5, 0.0));
+
+            let invalid_list = scrollable(column(self.invalid_sequences.iter().map(|invalid| {
+                text(format!(
+                    "{}: {} bytes at line {}, byte {}",
+                    invalid
+                        .path
+                        .as_deref()
+                        .map(|path| path.to_string_lossy())
+                        .unwrap_or_default(),
+                    invalid.length,
+                    invalid.line_number,
+                    invalid.total_byte_offset
+                ))
+                .into()
+            })))
+            .height(Length::Fill)
+            .width(Length::Fill);
+
+            content = content.push(invalid_title).push(invalid_list);
+        }
+
+        if !self.unparseable_values.is_empty() {
+            let unparseable_title = text(format!(
+                "Values the filters can't compare ({})",
+                self.unparseable_values.len()
+            ))
+            .size(18)
+            .color(iced::Color::from_rgb(0.8, 0.5, 0.0));
+
+            let unparseable_list =
+                scrollable(column(self.unparseable_values.iter().map(|unparseable| {
+                    text(format!(
+                        "{}: \"{}\" in record {}, column {} {}",
+                        unparseable
+                            .path
+                            .as_deref()
+                            .map(|path| path.to_string_lossy())
+                            .unwrap_or_default(),
+                        unparseable.value,
+                        unparseable.record_number,
+                        unparseable.column_number,
+                        unparseable.column_name.as_deref().unwrap_or_default()
+                    ))
+                    .into()
+                })))
+                .height(Length::Fill)
+                .width(Length::Fill);
+
+            content = content.push(unparseable_title).push(unparseable_list);
+        }
+
         content.padding(20).into()
     }
 
-    fn start_scan(
-        &mut self,
-        root: PathBuf,
-        search_string: String,
-        seperator: char,
-        token: CancellationToken,
-    ) -> Task<Message> {
-        let sipper = sipper(move |mut sender| async move {
-            let mut occurences: Vec<Occurence> = Vec::new();
+    /// The scanned bytes for the progress line, and how many were read from
+    /// disk if compressed files were decompressed
+    fn bytes_scanned(&self) -> String {
+        if self.read == self.scanned {
+            format!("{} bytes", self.scanned)
+        } else {
+            format!("{} bytes ({} bytes on disk)", self.scanned, self.read)
+        }
+    }
 
-            let file = match tokio::fs::File::open(root.as_path()).await {
-                Ok(file) => file,
-                Err(err) => {
-                    sender.send(Message::Error(err.to_string())).await;
-                    return;
-                }
-            };
-            let mut reader = BufReader::with_capacity(1024 * 1024, file);
-
-            // The characters we're searching for
-            let search_chars = search_string.to_lowercase().chars().collect::<Vec<_>>();
-            // Which line we're currently on
-            let mut line_number = 1u64;
-            // Which character we're currently on in the line
-            let mut line_character_offset = 0u64;
-            // Which byte that character is at
-            let mut line_byte_offset = 0u64;
-            // Which byte that character is at in total
-            let mut total_byte_offset = 0u64;
-            // Buffer to store already read characters
-            let mut found = VecDeque::<char>::new();
-            // which char in the search_chars we're currently on
-            let mut compare_index = 0;
-            let mut last_update_sent_bytes = 0u64;
+    /// The configuration a new scan would be started with
+    fn scan_config(&self) -> ScanConfig {
+        let terms = if self.multiple_terms {
+            self.search_terms.clone()
+        } else {
+            vec![self.search_string.clone()]
+        };
 
-            token
-                .run_until_cancelled(async move {
-                    // reserved space for a single character
-                    let mut unicode_character_bytes = [0u8; 4];
-                    loop {
-                        // send periodic updates to GUI
-                        if total_byte_offset - last_update_sent_bytes > 1024 * 1024 {
-                            sender
-                                .send(Message::ScanUpdate {
-                                    now_scanned: total_byte_offset,
-                                    occurences: mem::take(&mut occurences),
-                                })
-                                .await;
-                            last_update_sent_bytes = total_byte_offset;
-                        }
+        ScanConfig::with_terms(terms)
+            .mode(self.mode)
+            .max_distance(self.max_distance)
+            .phonetic(self.phonetic)
+            .case(self.case)
+            .normalization(self.normalization)
+            .ignore_diacritics(self.ignore_diacritics)
+            .scope(self.scope)
+            .trim(self.trim)
+            .overlapping(self.overlapping)
+            .seperator(self.seperator)
+            .quote(self.quote)
+            .has_header(self.has_header)
+            .encoding(self.encoding)
+            .lossy(self.lossy)
+            .file_patterns(self.file_patterns.split_whitespace())
+            .member_patterns(self.member_patterns.split_whitespace())
+            .columns(
+                self.columns
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (_, selected))| *selected)
+                    .map(|(index, _)| Column::Number(index as u64 + 1)),
+            )
+            .filters(self.parse_filters().unwrap_or_default())
+            .decimal_separator(self.decimal_separator)
+    }
 
-                        // read the first byte of the character
-                        let first_byte = match reader.read_u8().await {
-                            Ok(byte) => byte,
-                            Err(err) => {
-                                if err.kind() == std::io::ErrorKind::UnexpectedEof {
-                                    break;
-                                }
-                                sender.send(Message::Error(err.to_string())).await;
-                                return;
-                            }
-                        };
-
-                        // check how many bytes are needed for the character
-                        let len = match utf8_char_len(first_byte) {
-                            Some(len) => len,
-                            None => {
-                                sender
-                                    .send(Message::Error("Invalid UTF-8 sequence".to_string()))
-                                    .await;
-                                return;
-                            }
-                        };
-
-                        // how many characters in we are
-                        line_character_offset += 1;
-                        // how many bytes that character is at
-                        line_byte_offset += len as u64;
-                        // Which byte that character is at in total
-                        total_byte_offset += len as u64;
-
-                        unicode_character_bytes[0] = first_byte;
-                        if len > 1 {
-                            match reader
-                                .read_exact(&mut unicode_character_bytes[1..len])
-                                .await
-                            {
-                                Ok(_) => (),
-                                Err(err) => {
-                                    sender.send(Message::Error(err.to_string())).await;
-                                    return;
-                                }
-                            }
-                        }
+    /// The filters entered, separated by semicolons
+    fn parse_filters(&self) -> Result<Vec<Filter>, String> {
+        let date_format = self.date_format.parse::<DateFormat>()?;
+        self.filters
+            .split(';')
+            .filter(|filter| !filter.trim().is_empty())
+            .map(|filter| Filter::parse(filter, self.decimal_separator, &date_format))
+            .collect::<Result<_, ScanError>>()
+            .map_err(|err| err.to_string())
+    }
 
-                        let str = match std::str::from_utf8(&unicode_character_bytes[..len]) {
-                            Ok(s) => s,
-                            Err(err) => {
-                                sender.send(Message::Error(err.to_string())).await;
-                                return;
-                            }
-                        };
+    /// Shows invalid filters and date formats before a scan is started
+    fn validate_filters(&mut self) {
+        self.filters_error = self.parse_filters().err();
+    }
 
-                        let char = str.chars().next().unwrap().to_lowercase().next().unwrap();
+    /// Checkboxes for the columns of the selected file, none checked means
+    /// all columns are searched
+    fn column_selection(&'_ self) -> iced::Element<'_, Message> {
+        if let Some(error) = &self.columns_error {
+            return text(error)
+                .size(14)
+                .color(iced::Color::from_rgb(0.8, 0.2, 0.2))
+                .into();
+        }
+        if self.columns.is_empty() {
+            return text("All").into();
+        }
 
-                        match char {
-                            '\n' => {
-                                line_number += 1;
-                                line_character_offset = 0;
-                                line_byte_offset = 0;
-                                compare_index = 0;
-                                found.clear();
-                            }
-                            char => {
-                                if char == seperator {
-                                    compare_index = 0;
-                                    found.clear();
-                                }
-                            }
-                        }
+        let checkboxes = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(index, (name, selected))| {
+                let label = if self.has_header {
+                    format!("{} {}", index + 1, name)
+                } else {
+                    format!("{}", index + 1)
+                };
+                checkbox(*selected)
+                    .label(label)
+                    .on_toggle(move |selected| Message::ColumnToggled(index, selected))
+                    .into()
+            });
 
-                        if char == search_chars[compare_index] {
-                            found.push_back(char);
-                            compare_index += 1;
-                            if compare_index >= search_chars.len() {
-                                occurences.push(Occurence {
-                                    line_number,
-                                    line_character_offset,
-                                    line_byte_offset,
-                                    total_byte_offset,
-                                });
-                            } else {
-                                continue;
-                            }
-                        }
+        scrollable(column(checkboxes).spacing(5))
+            .height(100)
+            .width(Length::Fill)
+            .into()
+    }
 
-                        if found.len() == 0 {
-                            continue;
-                        }
+    /// Reads the first record of the selected file, or the first file in the
+    /// selected folder, to offer its columns.
+    fn read_header(&mut self) -> Task<Message> {
+        let Some(root) = self.selected.clone() else {
+            return Task::none();
+        };
+        // the first record is read as a header even if it holds data, so the
+        // number of columns is known
+        let config = ScanConfig::with_terms(Vec::<String>::new())
+            .seperator(self.seperator)
+            .quote(self.quote)
+            .has_header(true)
+            .encoding(self.encoding)
+            .file_patterns(self.file_patterns.split_whitespace())
+            .member_patterns(self.member_patterns.split_whitespace());
 
-                        found.pop_front();
+        self.header_request += 1;
+        let request = self.header_request;
 
-                        compare_index = 0;
+        Task::future(async move {
+            let (sender, receiver) = oneshot::channel();
+            thread::spawn(move || {
+                let header = Scanner::new(config).and_then(|scanner| {
+                    let Some(path) = scanner.files(&root).next().transpose()? else {
+                        return Ok(Vec::new());
+                    };
+                    // the first member, if it's an archive
+                    let mut header = Ok(Vec::new());
+                    scanner.scan_path(&path, |scan| {
+                        header = scan.and_then(|mut scan| Ok(scan.read_header()?.to_vec()));
+                        ControlFlow::Break(())
+                    })?;
+                    header
+                });
+                let _ = sender.send(header.map_err(|err| err.to_string()));
+            });
 
-                        while found.len() > 0 {
-                            if search_chars[compare_index] == found[compare_index] {
-                                compare_index += 1;
-                                if compare_index >= found.len() {
-                                    break;
-                                }
-                            } else {
-                                compare_index = 0;
-                                found.pop_front();
-                            }
-                        }
+            Message::HeaderRead(
+                request,
+                receiver
+                    .await
+                    .unwrap_or_else(|_| Err("Failed to read the header".to_string())),
+            )
+        })
+    }
 
-                        found.clear();
-                    }
+    /// Shows invalid search terms before a scan is started
+    fn validate_search(&mut self) {
+        self.search_error = if matches!(
+            self.mode,
+            SearchMode::Regex | SearchMode::Phonetic | SearchMode::Query
+        ) {
+            self.scan_config()
+                .validate()
+                .err()
+                .map(|err| err.to_string())
+        } else {
+            None
+        };
+    }
+
+    /// Whether there's something to search for, which may be nothing but
+    /// the filters with an empty search string
+    fn has_search_terms(&self) -> bool {
+        if self.multiple_terms {
+            !self.search_terms.is_empty()
+        } else {
+            !self.search_string.is_empty() || !self.filters.trim().is_empty()
+        }
+    }
+
+    fn start_scan(
+        &mut self,
+        root: PathBuf,
+        scanner: Scanner,
+        token: CancellationToken,
+    ) -> Task<Message> {
+        let sipper = sipper(move |mut sender| async move {
+            let mut updates = scanner.stream(root);
 
-                    sender
-                        .send(Message::ScanUpdate {
-                            now_scanned: total_byte_offset,
-                            occurences: mem::take(&mut occurences),
-                        })
-                        .await;
+            token
+                .run_until_cancelled(async move {
+                    while let Some(update) = updates.next().await {
+                        // errors only concern a single file, the scan goes on
+                        match update {
+                            Ok(update) => sender.send(Message::ScanUpdate(update)).await,
+                            Err(err) => sender.send(Message::Error(err.to_string())).await,
+                        }
+                    }
                 })
                 .await;
         });
@@ -558,17 +1229,3 @@ impl UI {
         Task::sip(sipper, |value| value, |_| Message::ScanComplete)
     }
 }
-
-fn utf8_char_len(first: u8) -> Option<usize> {
-    if first & 0b1000_0000 == 0 {
-        Some(1) // 0xxxxxxx
-    } else if first & 0b1110_0000 == 0b1100_0000 {
-        Some(2) // 110xxxxx
-    } else if first & 0b1111_0000 == 0b1110_0000 {
-        Some(3) // 1110xxxx
-    } else if first & 0b1111_1000 == 0b1111_0000 {
-        Some(4) // 11110xxx
-    } else {
-        None // continuation byte or invalid leading byte
-    }
-}
@@ -0,0 +1,141 @@
+//! Runs the command line tool and checks what it writes and exits with.
+
+use std::{fs, path::Path, process::Command};
+
+use csv_searcher::export;
+use tempfile::TempDir;
+
+/// The exit code, stdout and stderr of a run with the arguments
+fn run(args: &[&str], dir: &Path) -> (i32, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_csv-searcher-cli"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+fn dir() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    fs::write(
+        dir.path().join("cities.csv"),
+        "id,city\n1,Berlin\n2,Hamburg\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("cities.ssv"),
+        "id;city\n1;Berlin, Germany\n",
+    )
+    .unwrap();
+    dir
+}
+
+#[test]
+fn found() {
+    let dir = dir();
+    let (code, stdout, _) = run(&["berlin", "cities.csv"], dir.path());
+    assert_eq!(code, 0);
+    assert_eq!(
+        stdout,
+        format!(
+            "{}\ncities.csv,16,2,8,8,2,2,city,berlin,6,Berlin,0\n",
+            export::HEADER
+        )
+    );
+
+    let (code, stdout, _) = run(&["--no-header", "berlin", "cities.csv"], dir.path());
+    assert_eq!(code, 0);
+    assert_eq!(stdout.lines().count(), 1);
+}
+
+#[test]
+fn nothing_found() {
+    let dir = dir();
+    let (code, stdout, _) = run(&["munich", "cities.csv"], dir.path());
+    assert_eq!(code, 1);
+    assert_eq!(stdout, format!("{}\n", export::HEADER));
+}
+
+#[test]
+fn errors() {
+    let dir = dir();
+    let (code, _, stderr) = run(&["berlin", "missing.csv"], dir.path());
+    assert_eq!(code, 2);
+    assert!(stderr.contains("missing.csv"));
+
+    // the other files are still scanned
+    let (code, stdout, _) = run(&["berlin", "missing.csv", "cities.csv"], dir.path());
+    assert_eq!(code, 2);
+    assert_eq!(stdout.lines().count(), 2);
+
+    let (code, stdout, stderr) = run(&["--regex", "(", "cities.csv"], dir.path());
+    assert_eq!(code, 2);
+    assert!(stdout.is_empty());
+    assert!(stderr.starts_with("Invalid pattern"));
+
+    fs::write(dir.path().join("terms.txt"), "\n").unwrap();
+    let (code, _, stderr) = run(&["--terms-file", "terms.txt", "cities.csv"], dir.path());
+    assert_eq!(code, 2);
+    assert!(stderr.starts_with("No search terms"));
+
+    let (code, _, _) = run(&["berlin"], dir.path());
+    assert_eq!(code, 2);
+}
+
+#[test]
+fn term_hits() {
+    let dir = dir();
+    fs::write(dir.path().join("terms.txt"), "berlin\nBerlin\nmunich\n").unwrap();
+    let (code, stdout, stderr) = run(&["-f", "terms.txt", "cities.csv"], dir.path());
+    assert_eq!(code, 0);
+    assert_eq!(stdout.lines().count(), 3);
+    assert_eq!(stderr, "Hits per term:\nberlin: 1\nBerlin: 1\nmunich: 0\n");
+
+    // a single term has no summary
+    let (_, _, stderr) = run(&["berlin", "cities.csv"], dir.path());
+    assert!(stderr.is_empty());
+}
+
+#[test]
+fn lossy() {
+    let dir = dir();
+    fs::write(
+        dir.path().join("damaged.csv"),
+        b"id,city\n1,\xff\n2,Berlin\n",
+    )
+    .unwrap();
+    let (code, stdout, stderr) = run(
+        &["--lossy", "--encoding", "utf-8", "berlin", "damaged.csv"],
+        dir.path(),
+    );
+    assert_eq!(code, 0);
+    assert_eq!(stdout.lines().count(), 2);
+    assert_eq!(
+        stderr,
+        "damaged.csv: Replaced 1 invalid bytes at line 2, byte 10\n"
+    );
+}
+
+#[test]
+fn separator() {
+    let dir = dir();
+    for flag in ["--separator", "--seperator", "-s"] {
+        let (code, stdout, _) = run(&[flag, ";", "germany", "cities.ssv"], dir.path());
+        assert_eq!(code, 0);
+        assert!(stdout.ends_with(",2,2,city,germany,7,Germany,0\n"));
+    }
+}
+
+#[test]
+fn output_file() {
+    let dir = dir();
+    let (code, stdout, _) = run(&["-o", "found.csv", "berlin", "cities.csv"], dir.path());
+    assert_eq!(code, 0);
+    assert!(stdout.is_empty());
+    let written = fs::read_to_string(dir.path().join("found.csv")).unwrap();
+    assert_eq!(written.lines().count(), 2);
+}
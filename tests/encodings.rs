@@ -0,0 +1,228 @@
+//! Scans text in the different encodings and checks where in the bytes of
+//! the input the matches are reported.
+
+use csv_searcher::{
+    encoding::Encoding,
+    scanner::{ScanConfig, ScanError, Scanner},
+};
+
+const TEXT: &str = "name,price\nMüller,€5\n";
+
+fn utf16(text: &str, bytes: fn(u16) -> [u8; 2]) -> Vec<u8> {
+    text.encode_utf16().flat_map(bytes).collect()
+}
+
+fn windows_1252(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|char| match char {
+            '€' => 0x80,
+            _ => u8::try_from(char).unwrap(),
+        })
+        .collect()
+}
+
+/// The record, the offset right after the match in the input, how many
+/// bytes it spans and the matched value of every match
+type Found = Vec<(u64, u64, u64, String)>;
+
+fn scan(config: ScanConfig, input: &[u8]) -> Result<Found, ScanError> {
+    Scanner::new(config)?
+        .scan(input)
+        .map(|occurence| {
+            occurence.map(|occurence| {
+                (
+                    occurence.record_number,
+                    occurence.total_byte_offset,
+                    occurence.match_byte_length,
+                    occurence.matched.to_string(),
+                )
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn offsets() {
+    let config = ScanConfig::with_terms(["müller".to_string(), "€".to_string()]);
+    let found = |ends: [u64; 2], lens: [u64; 2]| {
+        vec![
+            (2, ends[0], lens[0], "Müller".to_string()),
+            (2, ends[1], lens[1], "€".to_string()),
+        ]
+    };
+
+    for (encoding, input, expected) in [
+        (
+            Encoding::Auto,
+            TEXT.as_bytes().to_vec(),
+            found([18, 22], [7, 3]),
+        ),
+        (Encoding::Auto, windows_1252(TEXT), found([17, 19], [6, 1])),
+        (
+            Encoding::Windows1252,
+            windows_1252(TEXT),
+            found([17, 19], [6, 1]),
+        ),
+        // with a byte order mark, or detected by the zeros
+        (
+            Encoding::Auto,
+            utf16("\u{feff}name,price\nMüller,€5\n", u16::to_le_bytes),
+            found([36, 40], [12, 2]),
+        ),
+        (
+            Encoding::Auto,
+            utf16("\u{feff}name,price\nMüller,€5\n", u16::to_be_bytes),
+            found([36, 40], [12, 2]),
+        ),
+        (
+            Encoding::Auto,
+            utf16(TEXT, u16::to_le_bytes),
+            found([34, 38], [12, 2]),
+        ),
+        (
+            Encoding::Utf16Be,
+            utf16(TEXT, u16::to_be_bytes),
+            found([34, 38], [12, 2]),
+        ),
+    ] {
+        let config = config.clone().encoding(encoding);
+        assert_eq!(scan(config, &input).unwrap(), expected, "{}", encoding);
+    }
+
+    // only detected UTF-8 may turn out to be something else
+    let utf8 = config.clone().encoding(Encoding::Utf8);
+    assert!(matches!(
+        scan(utf8, &windows_1252(TEXT)),
+        Err(ScanError::InvalidUtf8 {
+            total_byte_offset: 12
+        })
+    ));
+
+    // Latin-1 has no euro sign
+    let input = windows_1252("name\nMüller\n");
+    let config = config.encoding(Encoding::Latin1);
+    assert_eq!(
+        scan(config, &input).unwrap(),
+        vec![(2, 11, 6, "Müller".to_string())]
+    );
+}
+
+#[test]
+fn windows_1252_after_detection() {
+    // nothing but ASCII in the first buffers read
+    let mut input = format!("name\n{}", "Schmidt\n".repeat(2000)).into_bytes();
+    let start = input.len() as u64;
+    input.extend_from_slice(b"M\xfcller\nM\xfcller\n");
+
+    assert_eq!(
+        scan(ScanConfig::new("müller"), &input).unwrap(),
+        vec![
+            (2002, start + 6, 6, "Müller".to_string()),
+            (2003, start + 13, 6, "Müller".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn windows_1252_within_field() {
+    // the characters before the invalid byte still take two bytes each
+    let input = [b"name\n", "üüü".as_bytes(), b"\xfcxyz\n"].concat();
+    assert_eq!(
+        scan(ScanConfig::new("xyz"), &input).unwrap(),
+        vec![(2, 15, 3, "xyz".to_string())]
+    );
+
+    let input = [b"name\n", "ü\r".as_bytes(), b"\xfcx\n"].concat();
+    assert_eq!(
+        scan(ScanConfig::new("\rü"), &input).unwrap(),
+        vec![(2, 9, 2, "\rü".to_string())]
+    );
+}
+
+#[test]
+fn lossy_utf8() {
+    let input = "name\nM\u{fc}ller \u{fc}\nM\u{fc}ller\n".as_bytes();
+    // a corrupt byte in place of the second "ü"
+    let input = [&input[..13], b"\xfc", &input[15..]].concat();
+    let scanner = Scanner::new(ScanConfig::new("müller").lossy(true)).unwrap();
+    let mut scan = scanner.scan(&input[..]);
+
+    let found = scan
+        .by_ref()
+        .map(|occurence| occurence.unwrap().total_byte_offset)
+        .collect::<Vec<_>>();
+    assert_eq!(found, vec![12, 22]);
+    let invalid = scan
+        .take_invalid_sequences()
+        .into_iter()
+        .map(|sequence| (sequence.line_number, sequence.total_byte_offset))
+        .collect::<Vec<_>>();
+    assert_eq!(invalid, vec![(2, 13)]);
+}
+
+/// The matches like [`scan`] and the line, byte in the line, byte in the
+/// input and length of every invalid sequence in lossy mode
+fn scan_lossy(config: ScanConfig, input: &[u8]) -> (Found, Vec<(u64, u64, u64, u64)>) {
+    let scanner = Scanner::new(config.lossy(true)).unwrap();
+    let mut scan = scanner.scan(input);
+    let found = scan
+        .by_ref()
+        .map(|occurence| {
+            let occurence = occurence.unwrap();
+            (
+                occurence.record_number,
+                occurence.total_byte_offset,
+                occurence.match_byte_length,
+                occurence.matched.to_string(),
+            )
+        })
+        .collect();
+    let invalid = scan
+        .take_invalid_sequences()
+        .into_iter()
+        .map(|sequence| {
+            (
+                sequence.line_number,
+                sequence.line_byte_offset,
+                sequence.total_byte_offset,
+                sequence.length,
+            )
+        })
+        .collect();
+    (found, invalid)
+}
+
+#[test]
+fn lossy_offsets() {
+    // a high surrogate without the low one, and a single byte at the end
+    let input = [
+        utf16("name\nMü", u16::to_le_bytes),
+        0xd800_u16.to_le_bytes().to_vec(),
+        utf16("ler\nMüller", u16::to_le_bytes),
+        vec![0x41],
+    ]
+    .concat();
+    let config = ScanConfig::with_terms(["müller".to_string(), "\u{fffd}l".to_string()])
+        .encoding(Encoding::Utf16Le);
+    assert_eq!(
+        scan_lossy(config, &input),
+        (
+            vec![
+                (2, 18, 4, "\u{fffd}l".to_string()),
+                (3, 36, 12, "Müller".to_string()),
+            ],
+            vec![(2, 4, 14, 2), (3, 12, 36, 1)]
+        )
+    );
+
+    // a sequence cut short by the next character
+    let input = b"name\nM\xc3ller\n";
+    let config = ScanConfig::new("m\u{fffd}ller").encoding(Encoding::Utf8);
+    assert_eq!(
+        scan_lossy(config, input),
+        (
+            vec![(2, 11, 6, "M\u{fffd}ller".to_string())],
+            vec![(2, 1, 6, 1)]
+        )
+    );
+}
@@ -0,0 +1,244 @@
+//! Scans directories, compressed files and archives and checks which files
+//! and members are scanned and where the matches are reported.
+
+use std::{fs, io::Write, ops::ControlFlow, path::Path};
+
+use csv_searcher::{
+    decompress::Compression,
+    scanner::{ScanConfig, Scanner},
+};
+use tempfile::TempDir;
+use zip::{ZipWriter, write::SimpleFileOptions};
+
+/// The path relative to `root` and the record number of every match in the
+/// files below it, with `!` before the name of an archive member
+fn scan(config: ScanConfig, root: &Path) -> Vec<(String, u64)> {
+    let scanner = Scanner::new(config).unwrap();
+    let mut found = Vec::new();
+    for path in scanner.files(root) {
+        scanner
+            .scan_path(&path.unwrap(), |scan| {
+                for occurence in scan.unwrap() {
+                    let occurence = occurence.unwrap();
+                    let path = occurence.path.unwrap();
+                    let path = path.strip_prefix(root).unwrap();
+                    found.push((path.display().to_string(), occurence.record_number));
+                }
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+    }
+    found
+}
+
+fn gzip(content: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(content).unwrap();
+    encoder.finish().unwrap()
+}
+
+#[test]
+fn compressed_files() {
+    let content = "id,city\n1,Berlin\n2,\"Bad\nBerlin\"\n".repeat(100);
+    let (start, end) = content.as_bytes().split_at(content.len() / 2);
+    let dir = TempDir::new().unwrap();
+    let scanner = Scanner::new(ScanConfig::new("berlin")).unwrap();
+    let scan = |path: &Path| {
+        let scan = scanner.scan_file(path).unwrap();
+        let compression = scan.compression();
+        let found = scan
+            .map(|occurence| {
+                let occurence = occurence.unwrap();
+                (
+                    occurence.record_number,
+                    occurence.line_number,
+                    occurence.total_byte_offset,
+                )
+            })
+            .collect::<Vec<_>>();
+        (compression, found)
+    };
+
+    let plain = dir.path().join("plain.csv");
+    fs::write(&plain, &content).unwrap();
+    let (compression, expected) = scan(&plain);
+    assert_eq!(compression, None);
+    assert_eq!(expected.len(), 200);
+
+    let mut bzip2 = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+    bzip2.write_all(content.as_bytes()).unwrap();
+    let mut xz = liblzma::write::XzEncoder::new(Vec::new(), 6);
+    xz.write_all(content.as_bytes()).unwrap();
+
+    // whatever the files are named
+    for (name, compressed, compression) in [
+        ("data.csv.gz", gzip(content.as_bytes()), Compression::Gzip),
+        // several members are read one after another
+        (
+            "members.gz",
+            [gzip(start), gzip(end)].concat(),
+            Compression::Gzip,
+        ),
+        (
+            "data.zst",
+            zstd::encode_all(content.as_bytes(), 0).unwrap(),
+            Compression::Zstd,
+        ),
+        ("data", bzip2.finish().unwrap(), Compression::Bzip2),
+        ("data.csv", xz.finish().unwrap(), Compression::Xz),
+    ] {
+        let path = dir.path().join(name);
+        fs::write(&path, compressed).unwrap();
+        assert_eq!(
+            scan(&path),
+            (Some(compression), expected.clone()),
+            "{}",
+            name
+        );
+    }
+}
+
+fn write_zip(path: &Path, members: &[(&str, &str)]) {
+    let mut zip = ZipWriter::new(fs::File::create(path).unwrap());
+    for (name, content) in members {
+        zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+        zip.write_all(content.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap();
+}
+
+#[test]
+fn default_members() {
+    let dir = TempDir::new().unwrap();
+    write_zip(
+        &dir.path().join("export.zip"),
+        &[
+            ("data.csv", "city\nBerlin\n"),
+            ("logo.png", "city\nBerlin\n"),
+            ("notes/readme.txt", "city\nBerlin\n"),
+        ],
+    );
+
+    // only text files unless others are included
+    assert_eq!(
+        scan(ScanConfig::new("Berlin"), dir.path()),
+        vec![
+            ("export.zip!data.csv".to_string(), 2),
+            ("export.zip!notes/readme.txt".to_string(), 2),
+        ]
+    );
+    assert_eq!(
+        scan(
+            ScanConfig::new("Berlin").member_patterns(["!notes"]),
+            dir.path()
+        ),
+        vec![("export.zip!data.csv".to_string(), 2)]
+    );
+    assert_eq!(
+        scan(
+            ScanConfig::new("Berlin").member_patterns(["*.png"]),
+            dir.path()
+        ),
+        vec![("export.zip!logo.png".to_string(), 2)]
+    );
+}
+
+fn tar(members: &[(&str, &str)]) -> Vec<u8> {
+    let mut tar = tar::Builder::new(Vec::new());
+    for (name, content) in members {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        tar.append_data(&mut header, name, content.as_bytes())
+            .unwrap();
+    }
+    tar.into_inner().unwrap()
+}
+
+#[test]
+fn archive_members() {
+    let members = [
+        ("orders.csv", "id,city\n1,Berlin\n2,Bern\n"),
+        ("2024/returns.csv", "id,city\n1,Hamburg\n2,Berlin\n"),
+    ];
+    let dir = TempDir::new().unwrap();
+    write_zip(&dir.path().join("a.zip"), &members);
+    fs::write(dir.path().join("b.tar"), tar(&members)).unwrap();
+    fs::write(dir.path().join("c.tgz"), gzip(&tar(&members))).unwrap();
+    fs::write(dir.path().join("d.csv"), members[0].1).unwrap();
+
+    let found = scan(ScanConfig::new("berlin"), dir.path());
+    let expected = ["a.zip", "b.tar", "c.tgz"]
+        .into_iter()
+        .flat_map(|archive| {
+            [
+                (format!("{}!orders.csv", archive), 2),
+                (format!("{}!2024/returns.csv", archive), 3),
+            ]
+        })
+        .chain([("d.csv".to_string(), 2)])
+        .collect::<Vec<_>>();
+    assert_eq!(found, expected);
+
+    // members are filtered by their path in the archive
+    let config = ScanConfig::new("berlin")
+        .file_patterns(["*.tgz"])
+        .member_patterns(["2024/*.csv"]);
+    assert_eq!(
+        scan(config, dir.path()),
+        vec![("c.tgz!2024/returns.csv".to_string(), 3)]
+    );
+}
+
+/// The files below `root` the patterns select, relative to it
+fn files(patterns: &[&str], root: &Path) -> Vec<String> {
+    let scanner =
+        Scanner::new(ScanConfig::new("x").file_patterns(patterns.iter().copied())).unwrap();
+    scanner
+        .files(root)
+        .map(|path| {
+            let path = path.unwrap();
+            path.strip_prefix(root).unwrap().display().to_string()
+        })
+        .collect()
+}
+
+#[test]
+fn file_patterns() {
+    let dir = TempDir::new().unwrap();
+    for path in [
+        "a.csv",
+        "notes.txt",
+        "archive/old.csv",
+        "exports/2023-12.csv",
+        "exports/2024-01.csv",
+        "exports/archive/2024-02.csv",
+    ] {
+        let path = dir.path().join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "x\n").unwrap();
+    }
+    let root = dir.path();
+
+    assert_eq!(files(&[], root).len(), 6);
+    // a name matches in any directory, a path only from the root
+    assert_eq!(
+        files(&["*.csv", "!archive"], root),
+        vec!["a.csv", "exports/2023-12.csv", "exports/2024-01.csv"]
+    );
+    assert_eq!(
+        files(&["exports/**/2024-*.csv"], root),
+        vec!["exports/2024-01.csv", "exports/archive/2024-02.csv"]
+    );
+    assert_eq!(
+        files(&["!exports/archive", "!*.txt"], root),
+        vec![
+            "a.csv",
+            "archive/old.csv",
+            "exports/2023-12.csv",
+            "exports/2024-01.csv"
+        ]
+    );
+    // a file given as the root is scanned either way
+    assert_eq!(files(&["*.csv"], &root.join("notes.txt")), vec![""]);
+}
@@ -0,0 +1,551 @@
+//! Compares the matchers with a naive search for every term at every
+//! character of a field.
+
+use std::cmp::Reverse;
+
+use csv_searcher::scanner::{
+    CaseMode, DateFormat, DecimalSeparator, Filter, MatchScope, Normalization, Phonetic,
+    ScanConfig, ScanError, Scanner, SearchMode,
+};
+use proptest::{collection::btree_set, prelude::*};
+
+/// The term, end and length in characters of every match in a single field,
+/// in the order they are reported
+fn scan(config: ScanConfig, field: &str) -> Vec<(usize, u64, u64)> {
+    let scanner = Scanner::new(config.has_header(false).quote(None)).unwrap();
+    scanner
+        .scan(field.as_bytes())
+        .map(|occurence| {
+            let occurence = occurence.unwrap();
+            (
+                occurence.term,
+                occurence.line_character_offset,
+                occurence.match_length,
+            )
+        })
+        .collect()
+}
+
+/// Every term ending at every character, the longest first, and unless
+/// matches may overlap only those starting after the last one taken.
+fn naive(terms: &[String], field: &str, overlapping: bool) -> Vec<(usize, u64, u64)> {
+    let chars = field
+        .chars()
+        .map(|char| char.to_lowercase().next().unwrap())
+        .collect::<Vec<_>>();
+    let mut terms = terms
+        .iter()
+        .map(|term| term.to_lowercase().chars().collect::<Vec<_>>())
+        .enumerate()
+        .collect::<Vec<_>>();
+    terms.sort_by_key(|(_, term)| Reverse(term.len()));
+
+    let mut matches = Vec::new();
+    let mut last_end = 0;
+    for end in 1..=chars.len() {
+        for (index, term) in &terms {
+            let len = term.len();
+            if len <= end
+                && chars[end - len..end] == term[..]
+                && (overlapping || end - len >= last_end)
+            {
+                matches.push((*index, end as u64, len as u64));
+                last_end = end;
+            }
+        }
+    }
+    matches
+}
+
+#[test]
+fn repeated_prefix() {
+    let config = ScanConfig::new("aab");
+    assert_eq!(scan(config, "aaab"), vec![(0, 4, 3)]);
+}
+
+#[test]
+fn regex_matches() {
+    let config = ScanConfig::new(r"\d+").mode(SearchMode::Regex);
+    assert_eq!(scan(config, "12345 67"), vec![(0, 5, 5), (0, 8, 2)]);
+}
+
+#[test]
+fn overlapping_matches() {
+    assert_eq!(
+        scan(ScanConfig::new("aa"), "aaaa"),
+        vec![(0, 2, 2), (0, 3, 2), (0, 4, 2)]
+    );
+    assert_eq!(
+        scan(ScanConfig::new("aa").overlapping(false), "aaaa"),
+        vec![(0, 2, 2), (0, 4, 2)]
+    );
+}
+
+#[test]
+fn term_counts() {
+    let counts = |config: ScanConfig, field| {
+        let mut counts = vec![0; config.search_terms.len()];
+        for (term, _, _) in scan(config, field) {
+            counts[term] += 1;
+        }
+        counts
+    };
+
+    // a term given twice, also in another case, is credited to every copy
+    let terms = ["aa", "a", "AA", "b", "aa"];
+    assert_eq!(
+        counts(ScanConfig::with_terms(terms), "aaab"),
+        vec![2, 3, 2, 1, 2]
+    );
+    // also without overlaps
+    let terms = ["ab", "b", "AB"];
+    assert_eq!(
+        counts(ScanConfig::with_terms(terms).overlapping(false), "abab"),
+        vec![2, 0, 2]
+    );
+}
+
+#[test]
+fn case_modes() {
+    let exact = ScanConfig::new("Straße").case(CaseMode::Exact);
+    assert_eq!(
+        scan(exact.clone(), "STRASSE straße Straße"),
+        vec![(0, 21, 6)]
+    );
+
+    let lowercase = ScanConfig::new("Straße").case(CaseMode::Lowercase);
+    assert_eq!(
+        scan(lowercase, "STRASSE straße Straße"),
+        vec![(0, 14, 6), (0, 21, 6)]
+    );
+
+    // every match covers whole characters, like all of "ß"
+    let fold = ScanConfig::new("STRASSE").case(CaseMode::Fold);
+    assert_eq!(
+        scan(fold.clone(), "strasse straße"),
+        vec![(0, 7, 7), (0, 14, 6)]
+    );
+    assert_eq!(scan(ScanConfig::new("s").case(CaseMode::Fold), "ß"), vec![]);
+
+    let regex = fold.mode(SearchMode::Regex);
+    assert_eq!(scan(regex, "strasse straße"), vec![(0, 7, 7), (0, 14, 6)]);
+
+    // the whole lowercase form of 'İ' is compared
+    let dotted = ScanConfig::new("İ");
+    assert_eq!(scan(dotted.clone(), "i"), vec![]);
+    assert_eq!(scan(dotted, "i\u{307}İ"), vec![(0, 2, 2), (0, 3, 1)]);
+}
+
+#[test]
+fn normalization() {
+    let decomposed = "cafe\u{301}";
+    assert_eq!(scan(ScanConfig::new("café"), decomposed), vec![]);
+    assert_eq!(scan(ScanConfig::new("cafe"), decomposed), vec![(0, 4, 4)]);
+
+    // offsets and lengths count the characters of the field
+    let nfc = ScanConfig::new("café").normalization(Normalization::Nfc);
+    assert_eq!(scan(nfc, "café cafe\u{301}"), vec![(0, 4, 4), (0, 10, 5)]);
+    // the accent belongs to the last letter
+    let nfc = ScanConfig::new("cafe").normalization(Normalization::Nfc);
+    assert_eq!(scan(nfc, "café cafe\u{301} cafe"), vec![(0, 15, 4)]);
+
+    let nfkc = ScanConfig::new("fi").normalization(Normalization::Nfkc);
+    assert_eq!(
+        scan(nfkc.clone(), "\u{fb01} fi"),
+        vec![(0, 1, 1), (0, 4, 2)]
+    );
+    let nfc = nfkc.normalization(Normalization::Nfc);
+    assert_eq!(scan(nfc, "\u{fb01} fi"), vec![(0, 4, 2)]);
+}
+
+#[test]
+fn ignore_diacritics() {
+    let config = ScanConfig::new("Muller").ignore_diacritics(true);
+    assert_eq!(
+        scan(config, "Müller Mu\u{308}ller Muller"),
+        vec![(0, 6, 6), (0, 14, 7), (0, 21, 6)]
+    );
+
+    let config = ScanConfig::new("Müll").ignore_diacritics(true);
+    assert_eq!(scan(config, "Mu\u{308}ller"), vec![(0, 5, 5)]);
+
+    let regex = ScanConfig::new("^mül+er$")
+        .mode(SearchMode::Regex)
+        .ignore_diacritics(true);
+    assert_eq!(scan(regex, "Muller"), vec![(0, 6, 6)]);
+}
+
+#[test]
+fn scopes() {
+    let field = "2012 A1234 12-13";
+    assert_eq!(
+        scan(ScanConfig::new("12"), field),
+        vec![(0, 4, 2), (0, 8, 2), (0, 13, 2)]
+    );
+    let word = ScanConfig::new("12").scope(MatchScope::Word);
+    assert_eq!(scan(word, field), vec![(0, 13, 2)]);
+
+    let field = ScanConfig::new("12").scope(MatchScope::Field);
+    assert_eq!(scan(field.clone(), "12"), vec![(0, 2, 2)]);
+    assert_eq!(scan(field.clone(), " 12 "), vec![]);
+    assert_eq!(scan(field.trim(true), " 12 "), vec![(0, 3, 2)]);
+}
+
+#[test]
+fn wildcards() {
+    let config = ScanConfig::new("INV-2024-*-DE").mode(SearchMode::Wildcard);
+    assert_eq!(scan(config.clone(), "inv-2024-0815-de"), vec![(0, 16, 16)]);
+    assert_eq!(scan(config.clone(), "INV-2024-DE"), vec![]);
+    // the star takes as much as it can, and nothing inside of the match is
+    // reported again
+    assert_eq!(
+        scan(config, "INV-2024-1-DE/INV-2024-2-DE"),
+        vec![(0, 27, 27)]
+    );
+    let star = ScanConfig::new("*").mode(SearchMode::Wildcard);
+    assert_eq!(scan(star, "abc"), vec![(0, 3, 3)]);
+
+    // other characters are taken as they are, even if they mean something
+    // in a regex
+    let config = ScanConfig::new("a?c (1+1)").mode(SearchMode::Wildcard);
+    assert_eq!(
+        scan(config.clone(), "abc (1+1) a\u{e9}c (1+1) ac (1+1)"),
+        vec![(0, 9, 9), (0, 19, 9)]
+    );
+    let decomposed = config.normalization(Normalization::Nfc);
+    assert_eq!(scan(decomposed, "ae\u{301}c (1+1)"), vec![(0, 10, 10)]);
+}
+
+/// The matched value and its distance of every match in a single field
+fn scan_matched(config: ScanConfig, field: &str) -> Vec<(String, u32)> {
+    let scanner = Scanner::new(config.has_header(false).quote(None)).unwrap();
+    scanner
+        .scan(field.as_bytes())
+        .map(|occurence| {
+            let occurence = occurence.unwrap();
+            (occurence.matched.to_string(), occurence.distance)
+        })
+        .collect()
+}
+
+fn levenshtein(a: &str, b: &str) -> u32 {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len() as u32).collect::<Vec<_>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i as u32 + 1;
+        for j in 1..=b.len() {
+            let substituted = diagonal + u32::from(a != b[j - 1]);
+            diagonal = row[j];
+            row[j] = substituted.min(row[j] + 1).min(row[j - 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[test]
+fn fuzzy() {
+    let config = ScanConfig::new("Meier").mode(SearchMode::Fuzzy);
+    assert_eq!(
+        scan_matched(config.clone(), "Meyer Maier Meier Mayer"),
+        vec![
+            ("Meyer".to_string(), 1),
+            ("Maier".to_string(), 1),
+            ("Meier".to_string(), 0)
+        ]
+    );
+    assert_eq!(
+        scan_matched(config.clone().max_distance(2), "Mayer"),
+        vec![("Mayer".to_string(), 2)]
+    );
+
+    let field = config.scope(MatchScope::Field);
+    assert_eq!(
+        scan_matched(field.clone(), "Meiers"),
+        vec![("Meiers".to_string(), 1)]
+    );
+    assert_eq!(scan_matched(field, "Meierei"), vec![]);
+}
+
+#[test]
+fn phonetic() {
+    let matched = |config: ScanConfig, field| {
+        scan_matched(config.mode(SearchMode::Phonetic), field)
+            .into_iter()
+            .map(|(matched, _)| matched)
+            .collect::<Vec<_>>()
+    };
+
+    let soundex = ScanConfig::new("Robert").phonetic(Phonetic::Soundex);
+    assert_eq!(
+        matched(soundex, "Robert Rupert Rubin"),
+        ["Robert", "Rupert"]
+    );
+
+    let metaphone = ScanConfig::new("Smith").phonetic(Phonetic::DoubleMetaphone);
+    assert_eq!(
+        matched(metaphone, "Smith Schmitt Schneider"),
+        ["Smith", "Schmitt"]
+    );
+
+    let cologne = ScanConfig::new("Meier").phonetic(Phonetic::Cologne);
+    assert_eq!(
+        matched(cologne.clone(), "Mayer Maier Müller Meyer"),
+        ["Mayer", "Maier", "Meyer"]
+    );
+    let field = cologne.scope(MatchScope::Field);
+    assert_eq!(matched(field.clone(), "Hans Meier"), Vec::<String>::new());
+    assert_eq!(matched(field, "Mayr"), ["Mayr"]);
+
+    let words = ScanConfig::new("Müller-Lüdenscheidt").phonetic(Phonetic::Cologne);
+    assert_eq!(
+        matched(words, "Herr Mueller Luedenscheit"),
+        ["Mueller Luedenscheit"]
+    );
+
+    let unpronounceable = ScanConfig::new("1234").mode(SearchMode::Phonetic);
+    assert!(matches!(
+        Scanner::new(unpronounceable),
+        Err(ScanError::Unpronounceable(_))
+    ));
+}
+
+#[test]
+fn query() {
+    let csv = "id,country,status,amount\n\
+               1,DE,open,500\n\
+               2,DE,closed,1500\n\
+               3,FR,open,2000\n\
+               4,de,Open,\n";
+    let records = |query: &str| {
+        let scanner = Scanner::new(ScanConfig::new(query).mode(SearchMode::Query)).unwrap();
+        scanner
+            .scan(csv.as_bytes())
+            .map(|occurence| {
+                let occurence = occurence.unwrap();
+                (occurence.record_number, occurence.matched.to_string())
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        records("country = DE AND (status = open OR amount > 1000)"),
+        [
+            (2, "1,DE,open,500".to_string()),
+            (3, "2,DE,closed,1500".to_string()),
+            (5, "4,de,Open,".to_string())
+        ]
+    );
+    assert_eq!(
+        records("NOT status ~ o AND amount >= 2000"),
+        Vec::<(u64, String)>::new()
+    );
+    assert_eq!(records("amount = \"\" OR id = 3").len(), 2);
+
+    let invalid = |query: &str| Scanner::new(ScanConfig::new(query).mode(SearchMode::Query));
+    assert!(matches!(
+        invalid("country = DE AND"),
+        Err(ScanError::InvalidQuery { .. })
+    ));
+    assert!(matches!(
+        invalid("amount > lots"),
+        Err(ScanError::InvalidQuery { .. })
+    ));
+    let unknown = Scanner::new(ScanConfig::new("region = EU").mode(SearchMode::Query)).unwrap();
+    assert!(matches!(
+        unknown.scan(csv.as_bytes()).next(),
+        Some(Err(ScanError::UnknownColumn(_)))
+    ));
+}
+
+#[test]
+fn filters() {
+    let csv = "item;quantity;price\n\
+               A1;-2;1.234,56\n\
+               B2;5;999\n\
+               C3;x;2.000\n\
+               D4;3;4.500,5\n";
+    let filter =
+        |filter: &str| Filter::parse(filter, DecimalSeparator::Comma, &DateFormat::Auto).unwrap();
+    let scan = |search: &str, filters: &[&str]| {
+        let config = ScanConfig::new(search)
+            .seperator(';')
+            .decimal_separator(DecimalSeparator::Comma)
+            .filters(filters.iter().map(|&text| filter(text)));
+        let scanner = Scanner::new(config).unwrap();
+        let mut scan = scanner.scan(csv.as_bytes());
+        let found = scan
+            .by_ref()
+            .map(|occurence| {
+                let occurence = occurence.unwrap();
+                (occurence.record_number, occurence.matched.to_string())
+            })
+            .collect::<Vec<_>>();
+        let unparseable = scan
+            .take_unparseable_values()
+            .into_iter()
+            .map(|value| (value.record_number, value.value.to_string()))
+            .collect::<Vec<_>>();
+        (found, unparseable)
+    };
+
+    assert_eq!(
+        scan("", &["quantity < 0"]),
+        (
+            vec![(2, "A1;-2;1.234,56".to_string())],
+            vec![(4, "x".to_string())]
+        )
+    );
+    assert_eq!(
+        scan("", &["price between 1.000 and 5000", "2 > -5"]).0,
+        [
+            (2, "A1;-2;1.234,56".to_string()),
+            (5, "D4;3;4.500,5".to_string())
+        ]
+    );
+    assert_eq!(
+        scan("2", &["price > 1000"]).0,
+        [
+            (2, "2".to_string()),
+            (2, "2".to_string()),
+            (4, "2".to_string())
+        ]
+    );
+
+    for invalid in ["quantity", "< 3", "price between 5 and 1", "price = 1,2,3"] {
+        assert!(matches!(
+            Filter::parse(invalid, DecimalSeparator::Comma, &DateFormat::Auto),
+            Err(ScanError::InvalidFilter { .. })
+        ));
+    }
+    let unknown = ScanConfig::new("")
+        .has_header(false)
+        .filters([filter("price > 0")]);
+    assert!(matches!(
+        Scanner::new(unknown),
+        Err(ScanError::UnknownColumn(_))
+    ));
+}
+
+#[test]
+fn date_filters() {
+    let csv = "order;ordered;note\n\
+               1;2024-02-29T23:59:59Z;widget\n\
+               2;2024-03-01T00:30:00+01:00;widget\n\
+               3;2024-03-01 08:15;widget\n\
+               4;31.03.2024 18:00;gadget\n\
+               5;01 Apr 2024;widget\n\
+               6;soon;widget\n";
+    let scan = |search: &str, filter: &str| {
+        let filter = Filter::parse(filter, DecimalSeparator::Point, &DateFormat::Auto).unwrap();
+        let scanner =
+            Scanner::new(ScanConfig::new(search).seperator(';').filters([filter])).unwrap();
+        let mut scan = scanner.scan(csv.as_bytes());
+        let found = scan
+            .by_ref()
+            .map(|occurence| occurence.unwrap().record_number)
+            .collect::<Vec<_>>();
+        let unparseable = scan
+            .take_unparseable_values()
+            .into_iter()
+            .map(|value| value.value.to_string())
+            .collect::<Vec<_>>();
+        (found, unparseable)
+    };
+
+    assert_eq!(
+        scan("widget", "ordered between 2024-03-01 and 2024-03-31"),
+        (vec![4], vec!["soon".to_string()])
+    );
+    assert_eq!(scan("", "ordered = 2024-03-31").0, [5]);
+    assert_eq!(scan("", "ordered < 2024-03-01").0, [2, 3]);
+    assert_eq!(scan("", "ordered > 2024-03-31").0, [6]);
+    assert_eq!(scan("", "2 >= 2024-03-01 08:15").0, [4, 5, 6]);
+
+    let us = "%m/%d/%Y %I:%M %p".parse::<DateFormat>().unwrap();
+    let filter = Filter::parse("when > 03/15/2024 12:00 PM", DecimalSeparator::Point, &us);
+    let scanner = Scanner::new(ScanConfig::new("").filters([filter.unwrap()])).unwrap();
+    let found = scanner
+        .scan("when\n03/15/2024 02:30 PM\n03/15/2024 11:30 AM\n".as_bytes())
+        .map(|occurence| occurence.unwrap().matched.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(found, ["03/15/2024 02:30 PM"]);
+
+    assert!("%d.%m".parse::<DateFormat>().is_err());
+    assert!("%Y-%m-%d %Q".parse::<DateFormat>().is_err());
+    assert!(matches!(
+        Filter::parse(
+            "ordered between 2024-04-01 and 2024-03-01",
+            DecimalSeparator::Point,
+            &DateFormat::Auto
+        ),
+        Err(ScanError::InvalidFilter { .. })
+    ));
+}
+
+proptest! {
+    #[test]
+    fn single_term(term in "[ab]{1,5}", field in "[aAbB]{0,30}", overlapping: bool) {
+        let config = ScanConfig::new(term.clone()).overlapping(overlapping);
+        prop_assert_eq!(scan(config, &field), naive(&[term], &field, overlapping));
+    }
+
+    #[test]
+    fn several_terms(
+        terms in btree_set("[ab]{1,4}", 2..6),
+        field in "[aAbB]{0,30}",
+        overlapping: bool,
+    ) {
+        let terms = terms.into_iter().collect::<Vec<_>>();
+        let config = ScanConfig::with_terms(terms.clone()).overlapping(overlapping);
+        prop_assert_eq!(scan(config, &field), naive(&terms, &field, overlapping));
+    }
+
+    #[test]
+    fn regexes(
+        terms in btree_set("[ab]{1,4}", 1..6),
+        field in "[aAbB]{0,30}",
+        overlapping: bool,
+    ) {
+        let terms = terms.into_iter().collect::<Vec<_>>();
+        let config = ScanConfig::with_terms(terms.clone())
+            .mode(SearchMode::Regex)
+            .overlapping(overlapping);
+        // the matches of each regex never overlap, only those of different
+        // ones may
+        let mut expected = terms
+            .iter()
+            .enumerate()
+            .flat_map(|(index, term)| {
+                naive(std::slice::from_ref(term), &field, false)
+                    .into_iter()
+                    .map(move |(_, end, len)| (index, end, len))
+            })
+            .collect::<Vec<_>>();
+        expected.sort_by_key(|&(_, end, len)| (end, Reverse(len)));
+        let mut last_end = 0;
+        expected.retain(|&(_, end, len)| {
+            let taken = overlapping || end - len >= last_end;
+            if taken {
+                last_end = end;
+            }
+            taken
+        });
+        prop_assert_eq!(scan(config, &field), expected);
+    }
+
+    #[test]
+    fn fuzzy_fields(term in "[ab]{1,5}", field in "[ab]{1,8}", max_distance in 0..3u32) {
+        let config = ScanConfig::new(term.clone())
+            .mode(SearchMode::Fuzzy)
+            .max_distance(max_distance)
+            .scope(MatchScope::Field);
+        let distance = levenshtein(&term, &field);
+        let expected = if distance <= max_distance {
+            vec![(field.clone(), distance)]
+        } else {
+            vec![]
+        };
+        prop_assert_eq!(scan_matched(config, &field), expected);
+    }
+}
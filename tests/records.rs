@@ -0,0 +1,167 @@
+//! Scans small CSV inputs and checks which record and column the matches
+//! are reported in.
+
+use std::sync::Arc;
+
+use csv_searcher::scanner::{Column, MatchScope, Occurence, ScanConfig, ScanError, Scanner};
+use proptest::{collection::vec, prelude::*};
+
+/// The record and column number of every match
+fn scan(config: ScanConfig, input: &str) -> Vec<(u64, u64)> {
+    Scanner::new(config)
+        .unwrap()
+        .scan(input.as_bytes())
+        .map(|occurence| {
+            let occurence = occurence.unwrap();
+            (occurence.record_number, occurence.column_number)
+        })
+        .collect()
+}
+
+/// The record, column, line, the character in the line and the byte in the
+/// input right after it, and the length in bytes of every match
+fn locate(config: ScanConfig, input: &str) -> Vec<(u64, u64, u64, u64, u64, u64)> {
+    Scanner::new(config)
+        .unwrap()
+        .scan(input.as_bytes())
+        .map(|occurence| {
+            let occurence = occurence.unwrap();
+            (
+                occurence.record_number,
+                occurence.column_number,
+                occurence.line_number,
+                occurence.line_character_offset,
+                occurence.total_byte_offset,
+                occurence.match_byte_length,
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn quoted_fields() {
+    let input = "id,notes\r\n1,\"a, \"\"quoted\"\"\r\nBerlin\"\r\n2,Berlin\r\n";
+
+    // the line break inside of the quotes starts a line but not a record
+    assert_eq!(
+        locate(ScanConfig::new("Berlin"), input),
+        vec![(2, 2, 3, 6, 34, 6), (3, 2, 4, 8, 45, 6)]
+    );
+    // escaped quotes are matched as one, but span both in the input
+    assert_eq!(
+        locate(ScanConfig::new("\"quoted\""), input),
+        vec![(2, 2, 2, 16, 26, 9)]
+    );
+    assert_eq!(
+        locate(ScanConfig::new("\r\nBerlin"), input),
+        vec![(2, 2, 3, 6, 34, 8)]
+    );
+    assert_eq!(scan(ScanConfig::new("a,"), input), vec![(2, 2)]);
+}
+
+#[test]
+fn column_names() {
+    let names = |config: ScanConfig, input: &str| {
+        occurences(config, input)
+            .into_iter()
+            .map(|occurence| (occurence.column_number, occurence.column_name))
+            .collect::<Vec<_>>()
+    };
+    let input = "\u{feff}name,\"home city\"\nBerlin,Berlin\n";
+
+    // a byte order mark isn't part of the first name, quotes aren't either
+    assert_eq!(
+        names(ScanConfig::new("Berlin"), input),
+        vec![
+            (1, Some(Arc::from("name"))),
+            (2, Some(Arc::from("home city")))
+        ]
+    );
+    assert_eq!(
+        names(ScanConfig::new("Berlin").has_header(false), input),
+        vec![(1, None), (2, None)]
+    );
+    // a field beyond the header has no name
+    assert_eq!(names(ScanConfig::new("x"), "a\nb,x\n"), vec![(2, None)]);
+}
+
+#[test]
+fn columns_by_name() {
+    let input = "name, city ,notes\nBerlin,Berlin,Berlin\n";
+    let config = ScanConfig::new("Berlin").columns([Column::Name("city".to_string())]);
+    assert_eq!(scan(config, input), vec![(2, 2)]);
+
+    // names and numbers may be mixed
+    let config =
+        ScanConfig::new("Berlin").columns([Column::Name("notes".to_string()), Column::Number(1)]);
+    assert_eq!(scan(config, input), vec![(2, 1), (2, 3)]);
+
+    let config = ScanConfig::new("Berlin").columns([Column::Name("town".to_string())]);
+    let error = Scanner::new(config)
+        .unwrap()
+        .scan(input.as_bytes())
+        .find_map(Result::err);
+    assert!(matches!(error, Some(ScanError::UnknownColumn(_))));
+}
+
+#[test]
+fn columns_without_header() {
+    let config = ScanConfig::new("Berlin")
+        .has_header(false)
+        .columns([Column::Number(2)]);
+    assert_eq!(scan(config, "Berlin,x\nBerlin,Berlin\n"), vec![(2, 2)]);
+
+    let config = ScanConfig::new("Berlin")
+        .has_header(false)
+        .columns([Column::Name("city".to_string())]);
+    assert!(Scanner::new(config).is_err());
+}
+
+#[test]
+fn column_zero() {
+    assert!("0".parse::<Column>().is_err());
+    assert_eq!("2".parse(), Ok(Column::Number(2)));
+
+    let config = ScanConfig::new("Berlin").columns([Column::Number(0)]);
+    assert!(Scanner::new(config).is_err());
+}
+
+#[test]
+fn carriage_returns() {
+    // only a carriage return before a line feed belongs to the line break
+    let field = ScanConfig::new("x").scope(MatchScope::Field);
+    assert_eq!(
+        scan(field.clone(), "name,code\r\nBerlin,x\r\n"),
+        vec![(2, 2)]
+    );
+    assert_eq!(scan(field, "name,code\nBerlin,x\r\rx\n"), vec![]);
+
+    let input = "name,code\na\rb,\r\n";
+    assert_eq!(scan(ScanConfig::new("ab"), input), vec![]);
+    assert_eq!(scan(ScanConfig::new("a\rb"), input), vec![(2, 1)]);
+    assert_eq!(
+        scan(ScanConfig::new("\r"), "name\n\r,\r"),
+        vec![(2, 1), (2, 2)]
+    );
+}
+
+fn occurences(config: ScanConfig, input: &str) -> Vec<Occurence> {
+    Scanner::new(config)
+        .unwrap()
+        .scan(input.as_bytes())
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+proptest! {
+    #[test]
+    fn prefilter(term in "[bcd]{1,3}", records in vec("[bcdBCD ,\"\r\n]{0,12}", 0..20)) {
+        let input = format!("name,notes\n{}", records.join("\n"));
+        // a second term turns the prefilter off, and is never found
+        let unfiltered = ScanConfig::with_terms([term.clone(), "§".to_string()]);
+        prop_assert_eq!(
+            occurences(ScanConfig::new(term), &input),
+            occurences(unfiltered, &input)
+        );
+    }
+}
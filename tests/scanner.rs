@@ -0,0 +1,103 @@
+//! Uses the scanner the way a tool embedding it would, blocking on a reader
+//! or a file and in the background as a stream.
+
+use std::{fs, future, path::Path, pin::Pin};
+
+use csv_searcher::scanner::{Occurence, ScanConfig, ScanError, ScanUpdate, Scanner};
+use futures_core::Stream;
+use tempfile::TempDir;
+
+const INPUT: &str = "id,city\n1,Berlin\n2,Hamburg\n3,Berlin\n";
+
+/// Every update of a stream until it ends
+fn collect(scanner: &Scanner, path: &Path) -> Vec<Result<ScanUpdate, ScanError>> {
+    let mut stream = scanner.stream(path);
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let mut updates = Vec::new();
+        while let Some(update) = future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+            updates.push(update);
+        }
+        updates
+    })
+}
+
+#[test]
+fn blocking() {
+    let scanner = Scanner::new(ScanConfig::new("berlin")).unwrap();
+    let occurences = scanner
+        .scan(INPUT.as_bytes())
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(
+        occurences
+            .iter()
+            .map(|occurence| (occurence.record_number, occurence.matched.as_ref()))
+            .collect::<Vec<_>>(),
+        vec![(2, "Berlin"), (4, "Berlin")]
+    );
+    assert!(occurences.iter().all(|occurence| occurence.path.is_none()));
+
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("cities.csv");
+    fs::write(&path, INPUT).unwrap();
+    let from_file = scanner
+        .scan_file(&path)
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(from_file.len(), 2);
+    assert_eq!(from_file[0].path.as_deref(), Some(path.as_path()));
+    assert_eq!(
+        from_file[0].total_byte_offset,
+        occurences[0].total_byte_offset
+    );
+}
+
+#[test]
+fn stream() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("a.csv"), INPUT).unwrap();
+    fs::write(dir.path().join("b.csv"), "id,city\n4,Berlin\n").unwrap();
+    let scanner = Scanner::new(ScanConfig::new("berlin")).unwrap();
+
+    let updates = collect(&scanner, dir.path())
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let occurences = updates
+        .iter()
+        .flat_map(|update| &update.occurences)
+        .collect::<Vec<&Occurence>>();
+    assert_eq!(
+        occurences
+            .iter()
+            .map(|occurence| {
+                let path = occurence.path.as_deref().unwrap();
+                (
+                    path.file_name().unwrap().to_str().unwrap(),
+                    occurence.record_number,
+                )
+            })
+            .collect::<Vec<_>>(),
+        vec![("a.csv", 2), ("a.csv", 4), ("b.csv", 2)]
+    );
+
+    let last = updates.last().unwrap();
+    assert_eq!(last.files_scanned, 2);
+    assert_eq!(last.now_scanned, (INPUT.len() + 17) as u64);
+}
+
+#[test]
+fn stream_errors() {
+    let dir = TempDir::new().unwrap();
+    let scanner = Scanner::new(ScanConfig::new("berlin")).unwrap();
+
+    let updates = collect(&scanner, &dir.path().join("missing.csv"));
+    assert!(matches!(
+        updates.as_slice(),
+        [Err(ScanError::InFile { .. })]
+    ));
+}